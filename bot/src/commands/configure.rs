@@ -0,0 +1,155 @@
+use crate::commands::settings::format_settings;
+use crate::commands::utils::{check_role, CommandContext, DadMode, Role, RoomSettings};
+use crate::config::Config;
+use crate::errors::Error;
+use crate::triggers::get_locale_pack;
+use db::sea_orm::*;
+use db::utils as dbUtils;
+use db::utils::room_settings::RoomSetting;
+use matrix_sdk::ruma::events::{
+    room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+    AnyMessageEventContent,
+};
+use mrsbfh::commands::command;
+use mrsbfh::commands::extract::Extension;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+pub const REQUIRED_ROLE: Role = Role::Moderator;
+
+const USAGE: &str =
+    "Usage: `!configure <enabled|chance|love|locale|cooldown|mode> <value|default>`";
+
+#[command(help = "`!configure <setting> <value|default>` - Change how I behave in this room")]
+pub async fn configure<'a>(
+    Extension(tx): Extension<Arc<Mutex<mrsbfh::Sender>>>,
+    Extension(config): Extension<Arc<Mutex<Config<'a>>>>,
+    Extension(db): Extension<Arc<Mutex<DbConn>>>,
+    Extension(context): Extension<Arc<Mutex<CommandContext>>>,
+) -> Result<(), Error>
+where
+    Config<'a>: mrsbfh::config::Loader + Clone,
+{
+    let db = &*db.lock().await;
+    let config = &*config.lock().await;
+    let context = &*context.lock().await;
+    if !check_role(&tx, context, REQUIRED_ROLE).await? {
+        return Ok(());
+    }
+    let configure_resp = match parse_setting(context.args()) {
+        Ok(setting) => {
+            info!(
+                "{} changed {:?} in {}",
+                context.sender(),
+                setting,
+                context.room_id()
+            );
+            dbUtils::room_settings::set_room_setting(db, context.room_id(), setting).await?;
+            let settings = RoomSettings::load(db, config, context.room_id()).await?;
+            format_settings(&settings, config)
+        }
+        Err(e) => format!("{}\n\n{}", e, USAGE),
+    };
+    let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
+        TextMessageEventContent::markdown(configure_resp),
+    )));
+
+    tx.lock().await.send(content).await?;
+    Ok(())
+}
+
+fn parse_number(value: &str, min: i64) -> Result<Option<i64>, String> {
+    match value {
+        "default" => Ok(None),
+        value => match value.parse::<i64>() {
+            Ok(n) if n >= min => Ok(Some(n)),
+            _ => Err(format!(
+                "`{}` should be a number of at least {}",
+                value, min
+            )),
+        },
+    }
+}
+
+fn parse_setting(args: &[String]) -> Result<RoomSetting, String> {
+    let (key, value) = match args {
+        [key, value] => (key.to_lowercase(), value.to_lowercase()),
+        _ => return Err(String::from("I need a setting and a value")),
+    };
+    match key.as_str() {
+        "enabled" => match value.as_str() {
+            "on" | "true" | "yes" | "default" => Ok(RoomSetting::Enabled(true)),
+            "off" | "false" | "no" => Ok(RoomSetting::Enabled(false)),
+            _ => Err(format!("`{}` should be on or off", value)),
+        },
+        "chance" => parse_number(&value, 1).map(RoomSetting::DaddedChance),
+        "love" => parse_number(&value, 0).map(RoomSetting::LoveMeChance),
+        "cooldown" => parse_number(&value, 0).map(RoomSetting::Cooldown),
+        "locale" => match value.as_str() {
+            "default" => Ok(RoomSetting::Locale(None)),
+            code => match get_locale_pack(code) {
+                Some(pack) => Ok(RoomSetting::Locale(Some(pack.code.to_string()))),
+                None => Err(format!("I don't speak `{}`", code)),
+            },
+        },
+        "mode" => match value.as_str() {
+            "default" => Ok(RoomSetting::DadMode(None)),
+            mode => match DadMode::parse(mode) {
+                Some(mode) => Ok(RoomSetting::DadMode(Some(mode.as_str().to_string()))),
+                None => Err(format!("`{}` should be reply, react or both", mode)),
+            },
+        },
+        _ => Err(format!("I don't have a `{}` setting", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_parse_setting() -> Result<(), Error> {
+        let parse = |a: &[&str]| parse_setting(&args(a));
+        assert_eq!(parse(&["enabled", "off"]), Ok(RoomSetting::Enabled(false)));
+        assert_eq!(
+            parse(&["chance", "10"]),
+            Ok(RoomSetting::DaddedChance(Some(10)))
+        );
+        assert_eq!(
+            parse(&["love", "default"]),
+            Ok(RoomSetting::LoveMeChance(None))
+        );
+        assert_eq!(
+            parse(&["cooldown", "0"]),
+            Ok(RoomSetting::Cooldown(Some(0)))
+        );
+        assert_eq!(
+            parse(&["Locale", "DE"]),
+            Ok(RoomSetting::Locale(Some(String::from("de"))))
+        );
+        assert_eq!(
+            parse(&["mode", "React"]),
+            Ok(RoomSetting::DadMode(Some(String::from("react"))))
+        );
+        assert_eq!(parse(&["mode", "default"]), Ok(RoomSetting::DadMode(None)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_setting_invalid() -> Result<(), Error> {
+        let parse = |a: &[&str]| parse_setting(&args(a));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["chance"]).is_err());
+        assert!(parse(&["chance", "0"]).is_err());
+        assert!(parse(&["love", "-1"]).is_err());
+        assert!(parse(&["locale", "xx"]).is_err());
+        assert!(parse(&["mode", "wave"]).is_err());
+        assert!(parse(&["volume", "11"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::commands::utils::{check_role, CommandContext, Role};
+use crate::errors::Error;
+use db::sea_orm::*;
+use db::utils as dbUtils;
+use matrix_sdk::ruma::events::{
+    room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+    AnyMessageEventContent,
+};
+use mrsbfh::commands::command;
+use mrsbfh::commands::extract::Extension;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+pub const REQUIRED_ROLE: Role = Role::Everyone;
+
+const USAGE: &str = "Usage: `!dad <optout|optin> [here]`";
+
+#[derive(Debug, Clone, PartialEq)]
+enum DadAction {
+    OptOut { here: bool },
+    OptIn { here: bool },
+}
+
+impl DadAction {
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let here = match args.get(1).map(|arg| arg.to_lowercase()) {
+            None => false,
+            Some(arg) if arg == "here" => true,
+            Some(_) => return None,
+        };
+        match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            Some("optout") => Some(DadAction::OptOut { here }),
+            Some("optin") => Some(DadAction::OptIn { here }),
+            _ => None,
+        }
+    }
+}
+
+#[command(help = "`!dad <optout|optin> [here]` - Stop or start dadding you everywhere or here")]
+pub async fn dad(
+    Extension(tx): Extension<Arc<Mutex<mrsbfh::Sender>>>,
+    Extension(db): Extension<Arc<Mutex<DbConn>>>,
+    Extension(context): Extension<Arc<Mutex<CommandContext>>>,
+) -> Result<(), Error> {
+    let db = &*db.lock().await;
+    let context = &*context.lock().await;
+    if !check_role(&tx, context, REQUIRED_ROLE).await? {
+        return Ok(());
+    }
+    let dad_resp = match DadAction::from_args(context.args()) {
+        Some(action) => handle_action(db, context, action).await?,
+        None => String::from(USAGE),
+    };
+    let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
+        TextMessageEventContent::markdown(dad_resp),
+    )));
+
+    tx.lock().await.send(content).await?;
+    Ok(())
+}
+
+async fn handle_action(
+    db: &DbConn,
+    context: &CommandContext,
+    action: DadAction,
+) -> Result<String, Error> {
+    let sender = context.sender();
+    let room_id = context.room_id();
+    let scope = |here: bool| if here { Some(room_id.as_str()) } else { None };
+    let resp = match action {
+        DadAction::OptOut { here } => {
+            dbUtils::opt_outs::opt_out(db, sender, scope(here)).await?;
+            if here {
+                String::from("Okay kiddo, I won't dad you in this room anymore")
+            } else {
+                String::from("Okay kiddo, I won't dad you anywhere anymore")
+            }
+        }
+        DadAction::OptIn { here } => {
+            dbUtils::opt_outs::opt_in(db, sender, scope(here)).await?;
+            if dbUtils::opt_outs::is_opted_out(db, sender, room_id).await? {
+                String::from(
+                    "You're still opted out everywhere, use `!dad optin` to let me dad you again",
+                )
+            } else {
+                String::from("Welcome back kiddo!")
+            }
+        }
+    };
+    debug!("{} ran {:?} in {}", sender, action, room_id);
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::create_inmemory_db;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn context(msg: &str) -> CommandContext {
+        CommandContext::new(
+            String::from("!room:example.org"),
+            String::from("@alice:example.org"),
+            Role::Everyone,
+            msg,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_action_from_args() -> Result<(), Error> {
+        let action = |a: &[&str]| DadAction::from_args(&args(a));
+        assert_eq!(action(&["optout"]), Some(DadAction::OptOut { here: false }));
+        assert_eq!(
+            action(&["OptIn", "here"]),
+            Some(DadAction::OptIn { here: true })
+        );
+        assert_eq!(action(&["optout", "there"]), None);
+        assert_eq!(action(&["nap"]), None);
+        assert_eq!(action(&[]), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_opt_out_and_in() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let ctx = context("!dad optout");
+        let sender = ctx.sender().as_str();
+        let room_id = ctx.room_id().as_str();
+
+        handle_action(&db, &ctx, DadAction::OptOut { here: false }).await?;
+        assert!(dbUtils::opt_outs::is_opted_out(&db, sender, room_id).await?);
+        handle_action(&db, &ctx, DadAction::OptIn { here: true }).await?;
+        assert!(dbUtils::opt_outs::is_opted_out(&db, sender, room_id).await?);
+        handle_action(&db, &ctx, DadAction::OptIn { here: false }).await?;
+        assert!(!dbUtils::opt_outs::is_opted_out(&db, sender, room_id).await?);
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::commands::utils::DaddedManager;
+use crate::commands::utils::{check_role, CommandContext, DaddedManager, Role};
 use crate::config::Config;
 use crate::errors::Error;
 use matrix_sdk::ruma::events::{
@@ -8,13 +8,20 @@ use matrix_sdk::ruma::events::{
 use mrsbfh::commands::command;
 use mrsbfh::commands::extract::Extension;
 // use regex::Regex;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Local};
+use db::dadded::UNKNOWN_ROOM_ID;
 use db::sea_orm::*;
+use db::utils as dbUtils;
+use std::cmp;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::*;
 use getset::{Getters, Setters};
 
+pub const REQUIRED_ROLE: Role = Role::Everyone;
+const DEFAULT_HISTORY_LEN: u64 = 5;
+const MAX_HISTORY_LEN: u64 = 30;
+
 #[derive(Debug, Clone, Getters, Setters)]
 struct DadDurationText {
     #[getset(get = "pub", set)]
@@ -74,12 +81,13 @@ impl DadDurationText {
     }
 }
 
-#[command(help = "`!dadded` - How many times I've dadded today")]
+#[command(help = "`!dadded [all|history [n]]` - How often I've dadded here, everywhere or lately")]
 pub async fn dadded<'a>(
     Extension(tx): Extension<Arc<Mutex<mrsbfh::Sender>>>,
     Extension(config): Extension<Arc<Mutex<Config<'a>>>>,
     Extension(db): Extension<Arc<Mutex<DbConn>>>,
     Extension(dad_handler): Extension<Arc<Mutex<DaddedManager>>>,
+    Extension(context): Extension<Arc<Mutex<CommandContext>>>,
 ) -> Result<(), Error>
 where
     Config<'a>: mrsbfh::config::Loader + Clone,
@@ -87,7 +95,20 @@ where
     let db = &*db.lock().await;
     let dad_handler = &mut *dad_handler.lock().await;
     let config = &*config.lock().await;
-    let current_dads_resp = get_dads(db, dad_handler, config.get_epoch_length()).await?;
+    let context = &*context.lock().await;
+    if !check_role(&tx, context, REQUIRED_ROLE).await? {
+        return Ok(());
+    }
+    let epoch_len = config.get_epoch_length();
+    let args = context.args();
+    let current_dads_resp = match args.first().map(|arg| arg.as_str()) {
+        Some("all") => get_all_dads(db, dad_handler, epoch_len).await?,
+        Some("history") => {
+            let epoch_count = parse_history_len(args.get(1));
+            get_dad_history(db, context.room_id(), epoch_count, Local::now(), epoch_len).await?
+        }
+        _ => get_dads(db, dad_handler, context.room_id(), epoch_len).await?,
+    };
     let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
         TextMessageEventContent::markdown(current_dads_resp),
     )));
@@ -96,28 +117,154 @@ where
     Ok(())
 }
 
+fn format_times(count: u32) -> &'static str {
+    match count {
+        1 => "time",
+        _ => "times",
+    }
+}
+
+// Reactions are only mentioned once there are some, most rooms only ever get replies
+fn format_counts(count: u32, reactions: u32) -> String {
+    let mut counts = format!("{} {}", count, format_times(count));
+    if reactions > 0 {
+        counts.push_str(&format!(
+            " and reacted {} {}",
+            reactions,
+            format_times(reactions)
+        ));
+    }
+    counts
+}
+
+// Dads from before they were counted per room can't be told apart by room anymore
+fn format_unknown_room(count: u32) -> String {
+    match count {
+        0 => String::new(),
+        _ => format!(
+            ", plus {} {} from before I counted rooms apart",
+            count,
+            format_times(count)
+        ),
+    }
+}
+
+// Cooldown hits are counted since startup, they are only worth mentioning once there are some
+fn format_cooldown_hits(dad_mgr: &DaddedManager) -> String {
+    let room_hits = *dad_mgr.room_cooldown_hits();
+    let user_hits = *dad_mgr.user_cooldown_hits();
+    if room_hits == 0 && user_hits == 0 {
+        return String::new();
+    }
+    format!(
+        "\n\nSince I woke up cooldowns held me back {} {} in rooms and {} {} for people I had just dadded",
+        room_hits,
+        format_times(room_hits),
+        user_hits,
+        format_times(user_hits)
+    )
+}
+
+fn format_dads(
+    dad_mgr: &DaddedManager,
+    count: u32,
+    reactions: u32,
+    scope: &str,
+    epoch_len: Duration,
+) -> String {
+    let counts = format_counts(count, reactions);
+    if *dad_mgr.awake_since_last_epoch() {
+        format!(
+            "I've dadded {}{} in the past {}",
+            counts,
+            scope,
+            DadDurationText::new(epoch_len).get_text()
+        )
+    } else {
+        format!("I've dadded {}{} since my last nap", counts, scope)
+    }
+}
+
 async fn get_dads<'a>(
     db: &'a DbConn,
     dad_mgr: &'a mut DaddedManager,
+    room_id: &str,
     epoch_len: Duration,
 ) -> Result<String, Error> {
-    let dad = dad_mgr.get_current_dad(db).await?;
-    let times = match dad.count {
-        1 => "time",
-        _ => "times",
-    };
+    let dad = dad_mgr.get_current_dad(db, room_id).await?;
+    let unknown_room =
+        dbUtils::dadded::find_dad_from_epoch(db, *dad_mgr.epoch_id(), UNKNOWN_ROOM_ID)
+            .await?
+            .map_or(0, |dad| dad.count);
+    let resp = format!(
+        "{}{}",
+        format_dads(dad_mgr, dad.count, dad.reaction_count, "", epoch_len),
+        format_unknown_room(unknown_room)
+    );
+    info!("Responding to dad request: {}", resp);
+    Ok(resp)
+}
+
+async fn get_all_dads<'a>(
+    db: &'a DbConn,
+    dad_mgr: &'a mut DaddedManager,
+    epoch_len: Duration,
+) -> Result<String, Error> {
+    let total = dad_mgr.get_current_total(db).await?;
+    let reactions = dad_mgr.get_current_reaction_total(db).await?;
+    let resp = format!(
+        "{}{}",
+        format_dads(dad_mgr, total, reactions, " across all rooms", epoch_len),
+        format_cooldown_hits(dad_mgr)
+    );
+    info!("Responding to dad request: {}", resp);
+    Ok(resp)
+}
 
-    let resp = if *dad_mgr.awake_since_last_epoch() {
+fn parse_history_len(arg: Option<&String>) -> u64 {
+    arg.and_then(|n| n.parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .map(|n| cmp::min(n, MAX_HISTORY_LEN))
+        .unwrap_or(DEFAULT_HISTORY_LEN)
+}
+
+async fn get_dad_history(
+    db: &DbConn,
+    room_id: &str,
+    epoch_count: u64,
+    now: DateTime<Local>,
+    epoch_len: Duration,
+) -> Result<String, Error> {
+    let history = dbUtils::dadded::get_room_history(db, room_id, epoch_count).await?;
+    // Both are for the same latest epochs, in the same order
+    let unknown_room = dbUtils::dadded::get_room_history(db, UNKNOWN_ROOM_ID, epoch_count).await?;
+    // Epochs are newest first, each one lasts until the next one started (or until now)
+    let mut epoch_end = now;
+    let lines = history
+        .iter()
+        .zip(unknown_room.iter())
+        .map(|((epoch, count, reactions), (_, unknown_count, _))| {
+            let length = cmp::min(epoch_end - epoch.epoch, epoch_len);
+            epoch_end = epoch.epoch;
+            format!(
+                "- {} in the {} from {}{}",
+                format_counts(*count, *reactions),
+                DadDurationText::new(length).get_text(),
+                epoch.epoch.format("%Y-%m-%d %H:%M"),
+                format_unknown_room(*unknown_count)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let resp = if lines.is_empty() {
+        String::from("I haven't dadded anyone yet")
+    } else {
         format!(
-            "I've dadded {} {} in the past {}",
-            dad.count,
-            times,
-            DadDurationText::new(epoch_len).get_text()
+            "Here's how much I've dadded lately:\n\n{}",
+            lines.join("\n")
         )
-    } else {
-        format!("I've dadded {} {} since my last nap", dad.count, times)
     };
-    info!("Responding to dad request: {}", resp);
+    info!("Responding to dad history request: {}", resp);
     Ok(resp)
 }
 
@@ -182,19 +329,19 @@ mod tests {
         let db = create_inmemory_db().await?;
         let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
         let duration = Duration::days(1);
+        let room_id = "!room:example.org";
         let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
         let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
-        let dadded = dbUtils::dadded::get_or_create_dad_from_epoch(&db, epoch.id).await?;
-        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into(), dadded.id);
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
         let cur_time = cur_time + duration;
 
         mgr.check_for_epoch_update(&db, cur_time.into(), duration)
             .await?;
 
-        let dad_string_none = get_dads(&db, &mut mgr, duration).await?;
+        let dad_string_none = get_dads(&db, &mut mgr, room_id, duration).await?;
 
-        mgr.increment_dadded(&db).await?;
-        let dad_string_one = get_dads(&db, &mut mgr, duration).await?;
+        mgr.increment_dadded(&db, room_id).await?;
+        let dad_string_one = get_dads(&db, &mut mgr, room_id, duration).await?;
 
         assert_eq!(
             dad_string_none,
@@ -213,13 +360,13 @@ mod tests {
         let db = create_inmemory_db().await?;
         let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
         let duration = Duration::days(1);
+        let room_id = "!room:example.org";
         let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
         let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
-        let dadded = dbUtils::dadded::get_or_create_dad_from_epoch(&db, epoch.id).await?;
-        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into(), dadded.id);
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
 
-        mgr.increment_dadded(&db).await?;
-        let dad_string_one = get_dads(&db, &mut mgr, duration).await?;
+        mgr.increment_dadded(&db, room_id).await?;
+        let dad_string_one = get_dads(&db, &mut mgr, room_id, duration).await?;
 
         assert_eq!(
             dad_string_one,
@@ -228,4 +375,137 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_get_all_dads() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
+        let duration = Duration::days(1);
+        let room_id = "!room:example.org";
+        let other_room_id = "!other:example.org";
+        let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
+        let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
+
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_dadded(&db, other_room_id).await?;
+        let room_string = get_dads(&db, &mut mgr, room_id, duration).await?;
+        let all_string = get_all_dads(&db, &mut mgr, duration).await?;
+
+        assert_eq!(
+            room_string,
+            String::from("I've dadded 1 time since my last nap")
+        );
+        assert_eq!(
+            all_string,
+            String::from("I've dadded 2 times across all rooms since my last nap")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_dads_with_reactions() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
+        let duration = Duration::days(1);
+        let room_id = "!room:example.org";
+        let other_room_id = "!other:example.org";
+        let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
+        let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
+
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_reaction_dadded(&db, room_id).await?;
+        mgr.increment_reaction_dadded(&db, other_room_id).await?;
+        let room_string = get_dads(&db, &mut mgr, room_id, duration).await?;
+        let all_string = get_all_dads(&db, &mut mgr, duration).await?;
+
+        assert_eq!(
+            room_string,
+            String::from("I've dadded 1 time and reacted 1 time since my last nap")
+        );
+        assert_eq!(
+            all_string,
+            String::from(
+                "I've dadded 1 time and reacted 2 times across all rooms since my last nap"
+            )
+        );
+
+        let now = Local::now();
+        mgr.mark_dadded(room_id, "@alice:example.org", now);
+        mgr.check_cooldown(
+            room_id,
+            "@bob:example.org",
+            now,
+            Some(Duration::minutes(1)),
+            None,
+        );
+        mgr.check_cooldown(other_room_id, "@alice:example.org", now, None, None);
+        let all_string = get_all_dads(&db, &mut mgr, duration).await?;
+        assert!(all_string.ends_with(
+            "\n\nSince I woke up cooldowns held me back 1 time in rooms and 0 times for people I had just dadded"
+        ));
+
+        // Counted before dads were kept per room
+        mgr.increment_dadded(&db, UNKNOWN_ROOM_ID).await?;
+        mgr.increment_dadded(&db, UNKNOWN_ROOM_ID).await?;
+        let room_string = get_dads(&db, &mut mgr, room_id, duration).await?;
+        assert_eq!(
+            room_string,
+            String::from("I've dadded 1 time and reacted 1 time since my last nap, plus 2 times from before I counted rooms apart")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_history_len() -> Result<(), Error> {
+        assert_eq!(parse_history_len(None), DEFAULT_HISTORY_LEN);
+        assert_eq!(parse_history_len(Some(&String::from("3"))), 3);
+        assert_eq!(
+            parse_history_len(Some(&String::from("0"))),
+            DEFAULT_HISTORY_LEN
+        );
+        assert_eq!(
+            parse_history_len(Some(&String::from("lots"))),
+            DEFAULT_HISTORY_LEN
+        );
+        assert_eq!(
+            parse_history_len(Some(&String::from("1000"))),
+            MAX_HISTORY_LEN
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_dad_history() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
+        let duration = Duration::days(1);
+        let room_id = "!room:example.org";
+        let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
+        let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
+
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_dadded(&db, UNKNOWN_ROOM_ID).await?;
+        mgr.check_for_epoch_update(&db, next_epoch + Duration::hours(1), duration)
+            .await?;
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_reaction_dadded(&db, room_id).await?;
+        let new_epoch = dbUtils::epochs::get_latest_epochs(&db, 1).await?.remove(0);
+        let now = new_epoch.epoch + Duration::hours(1);
+
+        let history = get_dad_history(&db, room_id, 5, now, duration).await?;
+
+        let expected = format!(
+            "Here's how much I've dadded lately:\n\n- 2 times and reacted 1 time in the hour from {}\n- 1 time in the day from {}, plus 1 time from before I counted rooms apart",
+            new_epoch.epoch.format("%Y-%m-%d %H:%M"),
+            epoch.epoch.format("%Y-%m-%d %H:%M")
+        );
+        assert_eq!(history, expected);
+        Ok(())
+    }
 }
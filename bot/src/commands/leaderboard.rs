@@ -0,0 +1,173 @@
+use crate::commands::utils::{check_role, CommandContext, DaddedManager, Role};
+use crate::dadding::sanitize;
+use crate::errors::Error;
+use db::sea_orm::*;
+use db::utils as dbUtils;
+use matrix_sdk::ruma::events::{
+    room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+    AnyMessageEventContent,
+};
+use mrsbfh::commands::command;
+use mrsbfh::commands::extract::Extension;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+pub const REQUIRED_ROLE: Role = Role::Everyone;
+const LEADERBOARD_SIZE: usize = 10;
+// The spec caps user IDs at 255 characters
+const MXID_MAX_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, PartialEq)]
+enum LeaderboardScope {
+    CurrentEpoch,
+    LastEpochs(u64),
+    AllTime,
+}
+
+impl LeaderboardScope {
+    pub fn from_args(args: &[String]) -> Self {
+        match args.first().map(|arg| arg.as_str()) {
+            Some("all") => LeaderboardScope::AllTime,
+            Some(n) => match n.parse::<u64>() {
+                Ok(n) if n > 0 => LeaderboardScope::LastEpochs(n),
+                _ => LeaderboardScope::CurrentEpoch,
+            },
+            None => LeaderboardScope::CurrentEpoch,
+        }
+    }
+}
+
+#[command(help = "`!leaderboard [n|all]` - Who I've dadded most this epoch, in n epochs or ever")]
+pub async fn leaderboard(
+    Extension(tx): Extension<Arc<Mutex<mrsbfh::Sender>>>,
+    Extension(db): Extension<Arc<Mutex<DbConn>>>,
+    Extension(dad_handler): Extension<Arc<Mutex<DaddedManager>>>,
+    Extension(context): Extension<Arc<Mutex<CommandContext>>>,
+) -> Result<(), Error> {
+    let db = &*db.lock().await;
+    let dad_handler = &mut *dad_handler.lock().await;
+    let context = &*context.lock().await;
+    if !check_role(&tx, context, REQUIRED_ROLE).await? {
+        return Ok(());
+    }
+    let scope = LeaderboardScope::from_args(context.args());
+    let leaderboard_resp = get_leaderboard(db, dad_handler, context.room_id(), scope).await?;
+    let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
+        TextMessageEventContent::markdown(leaderboard_resp),
+    )));
+
+    tx.lock().await.send(content).await?;
+    Ok(())
+}
+
+async fn get_leaderboard<'a>(
+    db: &'a DbConn,
+    dad_mgr: &'a mut DaddedManager,
+    room_id: &str,
+    scope: LeaderboardScope,
+) -> Result<String, Error> {
+    let (epoch_ids, title) = match scope {
+        LeaderboardScope::CurrentEpoch => {
+            (Some(vec![*dad_mgr.epoch_id()]), String::from("this epoch"))
+        }
+        LeaderboardScope::LastEpochs(n) => {
+            let epochs = dbUtils::epochs::get_latest_epochs(db, n).await?;
+            let epoch_ids = epochs.iter().map(|e| e.id).collect::<Vec<_>>();
+            let title = match n {
+                1 => String::from("in the last epoch"),
+                _ => format!("in the last {} epochs", n),
+            };
+            (Some(epoch_ids), title)
+        }
+        LeaderboardScope::AllTime => (None, String::from("of all time")),
+    };
+    let board = dbUtils::victims::get_leaderboard(db, room_id, epoch_ids.as_deref()).await?;
+
+    let resp = if board.is_empty() {
+        format!("Nobody has been dadded {} yet", title)
+    } else {
+        let lines = board
+            .iter()
+            .take(LEADERBOARD_SIZE)
+            .enumerate()
+            .map(|(place, (mxid, count))| {
+                let times = match count {
+                    1 => "time",
+                    _ => "times",
+                };
+                // Localparts can hold markdown and a bare mxid pings its owner
+                let mxid = sanitize(mxid, MXID_MAX_LENGTH);
+                format!("{}. {} - {} {}", place + 1, mxid, count, times)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("Most dadded {}:\n\n{}", title, lines)
+    };
+    info!("Responding to leaderboard request: {}", resp);
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::create_inmemory_db;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[tokio::test]
+    async fn test_scope_from_args() -> Result<(), Error> {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            LeaderboardScope::from_args(&args(&[])),
+            LeaderboardScope::CurrentEpoch
+        );
+        assert_eq!(
+            LeaderboardScope::from_args(&args(&["all"])),
+            LeaderboardScope::AllTime
+        );
+        assert_eq!(
+            LeaderboardScope::from_args(&args(&["5"])),
+            LeaderboardScope::LastEpochs(5)
+        );
+        assert_eq!(
+            LeaderboardScope::from_args(&args(&["0"])),
+            LeaderboardScope::CurrentEpoch
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_leaderboard() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
+        let duration = Duration::days(1);
+        let room_id = "!room:example.org";
+        let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
+        let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch);
+
+        let empty = get_leaderboard(&db, &mut mgr, room_id, LeaderboardScope::CurrentEpoch).await?;
+
+        mgr.increment_victim(&db, room_id, "@alice:example.org")
+            .await?;
+        mgr.increment_victim(&db, room_id, "@bob:example.org")
+            .await?;
+        mgr.increment_victim(&db, room_id, "@bob:example.org")
+            .await?;
+        let alltime = get_leaderboard(&db, &mut mgr, room_id, LeaderboardScope::AllTime).await?;
+        mgr.increment_victim(&db, room_id, "@_b*d_:example.org")
+            .await?;
+        let board = get_leaderboard(&db, &mut mgr, room_id, LeaderboardScope::AllTime).await?;
+
+        assert_eq!(empty, String::from("Nobody has been dadded this epoch yet"));
+        assert_eq!(
+            alltime,
+            String::from(
+                "Most dadded of all time:\n\n1. @\u{2060}bob:example.org - 2 times\n2. @\u{2060}alice:example.org - 1 time"
+            )
+        );
+        assert!(board.contains("@\u{2060}\\_b\\*d\\_:example.org - 1 time"));
+        Ok(())
+    }
+}
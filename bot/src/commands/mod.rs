@@ -2,10 +2,18 @@
 // use crate::errors::Error;
 use mrsbfh::commands::command_generate;
 
+pub mod configure;
+pub mod dad;
 pub mod dadded;
+pub mod leaderboard;
+pub mod settings;
 pub mod utils;
 
 #[command_generate(bot_name = "Dad", description = "I'm your digital dad!")]
 enum Commands {
+    Configure,
+    Dad,
     Dadded,
+    Leaderboard,
+    Settings,
 }
@@ -0,0 +1,137 @@
+use crate::commands::utils::{
+    check_role, CommandContext, DadMode, EnabledChance, Role, RoomSettings,
+};
+use crate::config::Config;
+use crate::errors::Error;
+use chrono::Duration;
+use db::sea_orm::*;
+use matrix_sdk::ruma::events::{
+    room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+    AnyMessageEventContent,
+};
+use mrsbfh::commands::command;
+use mrsbfh::commands::extract::Extension;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+pub const REQUIRED_ROLE: Role = Role::Everyone;
+
+#[command(help = "`!settings` - How I behave in this room")]
+pub async fn settings<'a>(
+    Extension(tx): Extension<Arc<Mutex<mrsbfh::Sender>>>,
+    Extension(config): Extension<Arc<Mutex<Config<'a>>>>,
+    Extension(db): Extension<Arc<Mutex<DbConn>>>,
+    Extension(context): Extension<Arc<Mutex<CommandContext>>>,
+) -> Result<(), Error>
+where
+    Config<'a>: mrsbfh::config::Loader + Clone,
+{
+    let db = &*db.lock().await;
+    let config = &*config.lock().await;
+    let context = &*context.lock().await;
+    if !check_role(&tx, context, REQUIRED_ROLE).await? {
+        return Ok(());
+    }
+    let settings = RoomSettings::load(db, config, context.room_id()).await?;
+    let settings_resp = format_settings(&settings, config);
+    let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
+        TextMessageEventContent::markdown(settings_resp),
+    )));
+
+    tx.lock().await.send(content).await?;
+    Ok(())
+}
+
+fn format_chance(chance: &Option<EnabledChance>, default: EnabledChance) -> String {
+    let (chance, suffix) = match chance {
+        Some(chance) => (chance.clone(), ""),
+        None => (default, " (default)"),
+    };
+    let chance_text = match chance {
+        EnabledChance::Off => String::from("never"),
+        EnabledChance::Always => String::from("always"),
+        EnabledChance::In(c) => format!("1 in {}", c),
+    };
+    format!("{}{}", chance_text, suffix)
+}
+
+pub(crate) fn format_settings(settings: &RoomSettings, config: &Config) -> String {
+    let enabled = match settings.enabled() {
+        true => "on",
+        false => "off",
+    };
+    let dadded_chance = format_chance(
+        settings.dadded_chance(),
+        EnabledChance::dadded(config.dadded_chance),
+    );
+    let love_me_chance = format_chance(
+        settings.love_me_chance(),
+        EnabledChance::love_me(config.love_me_chance),
+    );
+    let locale = match settings.locale() {
+        Some(locale) => locale.clone(),
+        None => String::from("any"),
+    };
+    let format_cooldown = |cooldown: &Option<Duration>| match cooldown {
+        Some(cooldown) => format!("{} seconds", cooldown.num_seconds()),
+        None => String::from("none"),
+    };
+    let cooldown = format_cooldown(settings.cooldown());
+    let user_cooldown = format_cooldown(settings.user_cooldown());
+    let dad_mode = match settings.dad_mode() {
+        DadMode::Reply => String::from("reply"),
+        mode => format!("{} with {}", mode.as_str(), config.get_reaction()),
+    };
+    debug!("Formatting settings: {:?}", settings);
+    format!(
+        "Settings for this room:\n\n\
+        - Dadding: {}\n\
+        - Dadded chance: {}\n\
+        - Love me chance: {}\n\
+        - Locale: {}\n\
+        - Cooldown: {}\n\
+        - Cooldown per person: {}\n\
+        - Mode: {}",
+        enabled, dadded_chance, love_me_chance, locale, cooldown, user_cooldown, dad_mode
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::{create_inmemory_db, load_example_config};
+    use db::utils as dbUtils;
+    use db::utils::room_settings::RoomSetting;
+
+    #[tokio::test]
+    async fn test_format_settings() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let mut config = load_example_config();
+        config.dadded_chance = Some(2);
+        config.love_me_chance = Some(2);
+        config.cooldown = None;
+        config.user_cooldown = Some(300);
+        let room_id = "!room:example.org";
+        dbUtils::room_settings::set_room_setting(&db, room_id, RoomSetting::DaddedChance(Some(10)))
+            .await?;
+        dbUtils::room_settings::set_room_setting(
+            &db,
+            room_id,
+            RoomSetting::Locale(Some(String::from("de"))),
+        )
+        .await?;
+
+        let settings = RoomSettings::load(&db, &config, room_id).await?;
+        let expected = "Settings for this room:\n\n\
+            - Dadding: on\n\
+            - Dadded chance: 1 in 10\n\
+            - Love me chance: 1 in 2 (default)\n\
+            - Locale: de\n\
+            - Cooldown: none\n\
+            - Cooldown per person: 300 seconds\n\
+            - Mode: reply";
+        assert_eq!(format_settings(&settings, &config), expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::commands::utils::Role;
+use getset::Getters;
+
+/// Information about the message that triggered the current command
+#[derive(Debug, Clone, Getters)]
+pub struct CommandContext {
+    #[getset(get = "pub")]
+    room_id: String,
+    #[getset(get = "pub")]
+    sender: String,
+    #[getset(get = "pub")]
+    role: Role,
+    // Lowercased command name if the message is a command
+    #[getset(get = "pub")]
+    command: Option<String>,
+    #[getset(get = "pub")]
+    args: Vec<String>,
+}
+
+impl CommandContext {
+    pub fn new(room_id: String, sender: String, role: Role, msg: &str) -> Self {
+        let mut words = msg.split_whitespace();
+        let command = words
+            .next()
+            .and_then(|word| word.strip_prefix('!'))
+            .filter(|command| !command.is_empty())
+            .map(|command| command.to_lowercase());
+        let args = words.map(|arg| arg.to_string()).collect();
+        Self {
+            room_id,
+            sender,
+            role,
+            command,
+            args,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    fn context(msg: &str) -> CommandContext {
+        CommandContext::new(
+            String::from("!room:example.org"),
+            String::from("@dad:example.org"),
+            Role::Everyone,
+            msg,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_args_skip_command() -> Result<(), Error> {
+        let ctx = context("!dadded  all  ");
+        assert_eq!(ctx.command(), &Some(String::from("dadded")));
+        assert_eq!(ctx.args(), &vec![String::from("all")]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_args() -> Result<(), Error> {
+        let ctx = context("!Dadded");
+        assert_eq!(ctx.command(), &Some(String::from("dadded")));
+        assert!(ctx.args().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_not_a_command() -> Result<(), Error> {
+        assert_eq!(context("I'm hungry").command(), &None);
+        assert_eq!(context("! dadded").command(), &None);
+        assert_eq!(context("").command(), &None);
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+/// How a dadded is delivered, as a text reply, a reaction to the trigger or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DadMode {
+    Reply,
+    React,
+    Both,
+}
+
+impl Default for DadMode {
+    fn default() -> Self {
+        DadMode::Reply
+    }
+}
+
+impl DadMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "reply" => Some(DadMode::Reply),
+            "react" => Some(DadMode::React),
+            "both" => Some(DadMode::Both),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DadMode::Reply => "reply",
+            DadMode::React => "react",
+            DadMode::Both => "both",
+        }
+    }
+
+    pub fn replies(&self) -> bool {
+        *self != DadMode::React
+    }
+
+    pub fn reacts(&self) -> bool {
+        *self != DadMode::Reply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    #[tokio::test]
+    async fn test_parse_dad_mode() -> Result<(), Error> {
+        assert_eq!(DadMode::parse("reply"), Some(DadMode::Reply));
+        assert_eq!(DadMode::parse("React"), Some(DadMode::React));
+        assert_eq!(DadMode::parse("BOTH"), Some(DadMode::Both));
+        assert_eq!(DadMode::parse("wave"), None);
+        assert_eq!(DadMode::parse(DadMode::Both.as_str()), Some(DadMode::Both));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dad_mode_delivery() -> Result<(), Error> {
+        assert!(DadMode::Reply.replies() && !DadMode::Reply.reacts());
+        assert!(!DadMode::React.replies() && DadMode::React.reacts());
+        assert!(DadMode::Both.replies() && DadMode::Both.reacts());
+        Ok(())
+    }
+}
@@ -2,10 +2,19 @@ use crate::errors::Error;
 use chrono::{DateTime, Duration, Local};
 use db::sea_orm::*;
 use db::utils as dbUtils;
-use db::Dadded;
+use db::utils::dad_events::NewDadEvent;
+use db::{DadEvent, Dadded, Victim};
 use getset::{Getters, Setters};
+use std::collections::HashMap;
 use tracing::*;
 
+/// Which cooldown kept a message from being dadded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cooldown {
+    Room,
+    User,
+}
+
 #[derive(Debug, Clone, Getters, Setters)]
 pub struct DaddedManager {
     #[getset(get = "pub", set)]
@@ -13,21 +22,70 @@ pub struct DaddedManager {
     #[getset(get = "pub", set)]
     next_epoch: DateTime<Local>,
     #[getset(get = "pub", set)]
-    dadded_id: u32,
-    #[getset(get = "pub", set)]
     awake_since_last_epoch: bool,
+    last_dadded_rooms: HashMap<String, DateTime<Local>>,
+    last_dadded_users: HashMap<(String, String), DateTime<Local>>,
+    #[getset(get = "pub")]
+    room_cooldown_hits: u32,
+    #[getset(get = "pub")]
+    user_cooldown_hits: u32,
 }
 
 impl DaddedManager {
-    pub fn new(epoch_id: u32, next_epoch: DateTime<Local>, dadded_id: u32) -> Self {
+    pub fn new(epoch_id: u32, next_epoch: DateTime<Local>) -> Self {
         Self {
             epoch_id,
             next_epoch,
-            dadded_id,
             awake_since_last_epoch: false,
+            last_dadded_rooms: HashMap::new(),
+            last_dadded_users: HashMap::new(),
+            room_cooldown_hits: 0,
+            user_cooldown_hits: 0,
+        }
+    }
+
+    /// Checks the room and then the user cooldown, counting every message a cooldown stops
+    pub fn check_cooldown(
+        &mut self,
+        room_id: &str,
+        mxid: &str,
+        now: DateTime<Local>,
+        room_cooldown: Option<Duration>,
+        user_cooldown: Option<Duration>,
+    ) -> Option<Cooldown> {
+        let is_cooling_down = |last_dadded: Option<&DateTime<Local>>,
+                               cooldown: Option<Duration>| {
+            match (last_dadded, cooldown) {
+                (Some(last_dadded), Some(cooldown)) => now < *last_dadded + cooldown,
+                _ => false,
+            }
+        };
+        let user_key = (room_id.to_owned(), mxid.to_owned());
+        if is_cooling_down(self.last_dadded_rooms.get(room_id), room_cooldown) {
+            self.room_cooldown_hits += 1;
+            info!(
+                "{} is cooling down, room cooldown hits: {}",
+                room_id, self.room_cooldown_hits
+            );
+            Some(Cooldown::Room)
+        } else if is_cooling_down(self.last_dadded_users.get(&user_key), user_cooldown) {
+            self.user_cooldown_hits += 1;
+            info!(
+                "{} is cooling down in {}, user cooldown hits: {}",
+                mxid, room_id, self.user_cooldown_hits
+            );
+            Some(Cooldown::User)
+        } else {
+            None
         }
     }
 
+    pub fn mark_dadded(&mut self, room_id: &str, mxid: &str, now: DateTime<Local>) {
+        self.last_dadded_rooms.insert(room_id.to_owned(), now);
+        self.last_dadded_users
+            .insert((room_id.to_owned(), mxid.to_owned()), now);
+    }
+
     pub async fn check_for_epoch_update(
         &mut self,
         db: &DbConn,
@@ -39,13 +97,10 @@ impl DaddedManager {
             let new_epoch = dbUtils::epochs::get_or_create_epoch(db, &now, epoch_len).await?;
             let next_epoch =
                 dbUtils::epochs::get_next_epoch_bound(db, new_epoch.id, epoch_len).await?;
-            let new_dadded =
-                dbUtils::dadded::get_or_create_dad_from_epoch(db, new_epoch.id).await?;
             let next_bound: DateTime<Local> = next_epoch;
             info!("Next Epoch boundry is now {}", next_bound);
             self.set_epoch_id(new_epoch.id);
             self.set_next_epoch(next_bound);
-            self.set_dadded_id(new_dadded.id);
             if !self.awake_since_last_epoch() {
                 self.set_awake_since_last_epoch(true);
             }
@@ -55,15 +110,83 @@ impl DaddedManager {
         }
     }
 
-    pub async fn increment_dadded(&mut self, db: &DbConn) -> Result<Dadded::Model, Error> {
-        let dad = dbUtils::dadded::increament_dadded(db, self.dadded_id).await?;
+    /// Moves the next epoch boundary to match a new epoch length
+    pub async fn update_epoch_length(
+        &mut self,
+        db: &DbConn,
+        epoch_len: Duration,
+    ) -> Result<(), Error> {
+        let next_bound =
+            dbUtils::epochs::get_next_epoch_bound(db, self.epoch_id, epoch_len).await?;
+        info!(
+            "Epoch length changed, next Epoch boundry is now {}",
+            next_bound
+        );
+        self.set_next_epoch(next_bound);
+        Ok(())
+    }
+
+    pub async fn increment_dadded(
+        &mut self,
+        db: &DbConn,
+        room_id: &str,
+    ) -> Result<Dadded::Model, Error> {
+        let cur_dad = self.get_current_dad(db, room_id).await?;
+        let dad = dbUtils::dadded::increament_dadded(db, cur_dad.id).await?;
+        Ok(dad)
+    }
+
+    pub async fn increment_reaction_dadded(
+        &mut self,
+        db: &DbConn,
+        room_id: &str,
+    ) -> Result<Dadded::Model, Error> {
+        let cur_dad = self.get_current_dad(db, room_id).await?;
+        let dad = dbUtils::dadded::increament_reaction_dadded(db, cur_dad.id).await?;
         Ok(dad)
     }
 
-    pub async fn get_current_dad(&mut self, db: &DbConn) -> Result<Dadded::Model, Error> {
-        let dad = dbUtils::dadded::get_or_create_dad_from_epoch(db, self.epoch_id).await?;
+    pub async fn get_current_dad(
+        &mut self,
+        db: &DbConn,
+        room_id: &str,
+    ) -> Result<Dadded::Model, Error> {
+        let dad = dbUtils::dadded::get_or_create_dad_from_epoch(db, self.epoch_id, room_id).await?;
         Ok(dad)
     }
+
+    pub async fn increment_victim(
+        &mut self,
+        db: &DbConn,
+        room_id: &str,
+        mxid: &str,
+    ) -> Result<Victim::Model, Error> {
+        let cur_victim =
+            dbUtils::victims::get_or_create_victim_from_epoch(db, self.epoch_id, room_id, mxid)
+                .await?;
+        let victim = dbUtils::victims::increament_victim(db, cur_victim.id).await?;
+        Ok(victim)
+    }
+
+    pub async fn log_dad_event(
+        &mut self,
+        db: &DbConn,
+        now: DateTime<Local>,
+        dad_event: NewDadEvent,
+    ) -> Result<DadEvent::Model, Error> {
+        let event = dbUtils::dad_events::create_dad_event(db, &now, dad_event).await?;
+        Ok(event)
+    }
+
+    pub async fn get_current_total(&mut self, db: &DbConn) -> Result<u32, Error> {
+        let total = dbUtils::dadded::get_total_dads_from_epoch(db, self.epoch_id).await?;
+        Ok(total)
+    }
+
+    pub async fn get_current_reaction_total(&mut self, db: &DbConn) -> Result<u32, Error> {
+        let total = dbUtils::dadded::get_total_reactions_from_epoch(db, self.epoch_id).await?;
+        Ok(total)
+    }
 }
 
 #[cfg(test)]
@@ -79,8 +202,7 @@ mod tests {
         let duration = Duration::days(1);
         let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
         let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
-        let dadded = dbUtils::dadded::get_or_create_dad_from_epoch(&db, epoch.id).await?;
-        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into(), dadded.id);
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
 
         let cur_time = cur_time + duration;
 
@@ -90,7 +212,6 @@ mod tests {
 
         assert_eq!(epoch_changed, true);
         assert_eq!(*mgr.epoch_id(), epoch.id + 1);
-        assert_eq!(*mgr.dadded_id(), dadded.id + 1);
         assert_eq!(*mgr.next_epoch(), next_epoch + duration);
         assert_eq!(*mgr.awake_since_last_epoch(), true);
 
@@ -105,8 +226,7 @@ mod tests {
         let time_passed = Duration::hours(1);
         let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
         let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
-        let dadded = dbUtils::dadded::get_or_create_dad_from_epoch(&db, epoch.id).await?;
-        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into(), dadded.id);
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch.into());
 
         let cur_time = cur_time + time_passed;
 
@@ -116,10 +236,93 @@ mod tests {
 
         assert_eq!(epoch_changed, false);
         assert_eq!(*mgr.epoch_id(), epoch.id);
-        assert_eq!(*mgr.dadded_id(), dadded.id);
         assert_eq!(*mgr.next_epoch(), next_epoch);
         assert_eq!(*mgr.awake_since_last_epoch(), false);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_increment_dadded_is_scoped_to_room() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let cur_time = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300);
+        let duration = Duration::days(1);
+        let room_id = "!room:example.org";
+        let other_room_id = "!other:example.org";
+        let epoch = dbUtils::epochs::get_or_create_epoch(&db, &cur_time.into(), duration).await?;
+        let next_epoch = dbUtils::epochs::get_next_epoch_bound(&db, epoch.id, duration).await?;
+        let mut mgr = DaddedManager::new(epoch.id, next_epoch);
+
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_dadded(&db, room_id).await?;
+        mgr.increment_dadded(&db, other_room_id).await?;
+
+        assert_eq!(mgr.get_current_dad(&db, room_id).await?.count, 2);
+        assert_eq!(mgr.get_current_dad(&db, other_room_id).await?.count, 1);
+        assert_eq!(mgr.get_current_total(&db).await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_room_cooldown() -> Result<(), Error> {
+        let cur_time: DateTime<Local> = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300).into();
+        let cooldown = Some(Duration::seconds(30));
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+        let mut mgr = DaddedManager::new(1, cur_time + Duration::days(1));
+
+        assert_eq!(
+            mgr.check_cooldown(room_id, alice, cur_time, cooldown, None),
+            None
+        );
+        mgr.mark_dadded(room_id, alice, cur_time);
+        let later = cur_time + Duration::seconds(10);
+        assert_eq!(
+            mgr.check_cooldown(room_id, bob, later, cooldown, None),
+            Some(Cooldown::Room)
+        );
+        assert_eq!(
+            mgr.check_cooldown("!other:example.org", bob, later, cooldown, None),
+            None
+        );
+        let later = cur_time + Duration::seconds(30);
+        assert_eq!(
+            mgr.check_cooldown(room_id, bob, later, cooldown, None),
+            None
+        );
+        assert_eq!(*mgr.room_cooldown_hits(), 1);
+        assert_eq!(*mgr.user_cooldown_hits(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_cooldown() -> Result<(), Error> {
+        let cur_time: DateTime<Local> = Utc.ymd(2022, 4, 1).and_hms_milli(19, 15, 10, 300).into();
+        let room_cooldown = Some(Duration::seconds(30));
+        let user_cooldown = Some(Duration::minutes(10));
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+        let mut mgr = DaddedManager::new(1, cur_time + Duration::days(1));
+
+        mgr.mark_dadded(room_id, alice, cur_time);
+        let later = cur_time + Duration::minutes(1);
+        let check = |mgr: &mut DaddedManager, mxid| {
+            mgr.check_cooldown(room_id, mxid, later, room_cooldown, user_cooldown)
+        };
+        assert_eq!(check(&mut mgr, alice), Some(Cooldown::User));
+        assert_eq!(check(&mut mgr, alice), Some(Cooldown::User));
+        assert_eq!(check(&mut mgr, bob), None);
+        assert_eq!(
+            mgr.check_cooldown("!other:example.org", alice, later, None, user_cooldown),
+            None
+        );
+        assert_eq!(*mgr.room_cooldown_hits(), 0);
+        assert_eq!(*mgr.user_cooldown_hits(), 2);
+
+        Ok(())
+    }
 }
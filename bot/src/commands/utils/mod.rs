@@ -1,4 +1,14 @@
-pub use self::dadded_manager::DaddedManager;
-pub use self::rng_manager::RngManager;
+pub use self::command_context::CommandContext;
+pub use self::dad_mode::DadMode;
+pub use self::dadded_manager::{Cooldown, DaddedManager};
+pub use self::permissions::{check_role, get_command_context};
+pub use self::rng_manager::{EnabledChance, RngManager};
+pub use self::role::Role;
+pub use self::room_settings::RoomSettings;
+mod command_context;
+mod dad_mode;
 mod dadded_manager;
+mod permissions;
 mod rng_manager;
+mod role;
+mod room_settings;
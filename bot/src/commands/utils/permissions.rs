@@ -0,0 +1,144 @@
+use crate::commands::utils::{CommandContext, Role};
+use crate::config::Config;
+use crate::errors::Error;
+use crate::transport::{IncomingMessage, Transport};
+use matrix_sdk::ruma::events::{
+    room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+    AnyMessageEventContent,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+async fn get_sender_role<Tr>(
+    transport: &Tr,
+    message: &IncomingMessage,
+    config: Arc<Mutex<Config<'static>>>,
+) -> Role
+where
+    Tr: Transport + ?Sized,
+{
+    let (is_owner, moderator_power_level) = {
+        let config = &*config.lock().await;
+        (
+            config.is_owner(&message.sender),
+            config.get_moderator_power_level(),
+        )
+    };
+    let power_level = transport
+        .power_level(&message.room_id, &message.sender)
+        .await;
+    Role::new(is_owner, power_level, moderator_power_level)
+}
+
+/// Works out who sent the message and what it asks for, only commands look up the sender's role
+pub async fn get_command_context<Tr>(
+    transport: &Tr,
+    message: &IncomingMessage,
+    config: Arc<Mutex<Config<'static>>>,
+) -> CommandContext
+where
+    Tr: Transport + ?Sized,
+{
+    let msg = message.body.trim_start();
+    let role = if msg.starts_with('!') {
+        get_sender_role(transport, message, config).await
+    } else {
+        Role::Everyone
+    };
+    CommandContext::new(message.room_id.clone(), message.sender.clone(), role, msg)
+}
+
+/// Checks the sender's role once a command was picked to run, telling them off when it isn't
+/// enough. Every command calls this with its own `REQUIRED_ROLE` before doing anything
+pub async fn check_role(
+    tx: &Mutex<mrsbfh::Sender>,
+    context: &CommandContext,
+    required: Role,
+) -> Result<bool, Error> {
+    if context.role().allows(required) {
+        return Ok(true);
+    }
+    warn!(
+        "{} tried to run {:?} in {} as {:?}, needs {:?}",
+        context.sender(),
+        context.command(),
+        context.room_id(),
+        context.role(),
+        required
+    );
+    let refusal = format!("Sorry, only {} can ask me that", required.describe());
+    let content = AnyMessageEventContent::RoomMessage(MessageEventContent::new(MessageType::Text(
+        TextMessageEventContent::plain(refusal),
+    )));
+    tx.lock().await.send(content).await?;
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::load_example_config;
+    use crate::transport::InMemoryTransport;
+    use tokio::sync::mpsc;
+
+    const ROOM_ID: &str = "!room:example.org";
+    const ALICE: &str = "@alice:example.org";
+
+    async fn context_for(transport: &InMemoryTransport, msg: &str) -> CommandContext {
+        let config = Arc::new(Mutex::new(load_example_config()));
+        let message = IncomingMessage::text(ROOM_ID, ALICE, "$event", msg);
+        get_command_context(transport, &message, config).await
+    }
+
+    #[tokio::test]
+    async fn test_role_from_power_level() -> Result<(), Error> {
+        let transport = InMemoryTransport::new("@dad:example.org");
+        assert_eq!(
+            context_for(&transport, "!configure enabled off")
+                .await
+                .role(),
+            &Role::Everyone
+        );
+        transport.set_power_level(ROOM_ID, ALICE, 50).await;
+        assert_eq!(
+            context_for(&transport, "!configure enabled off")
+                .await
+                .role(),
+            &Role::Moderator
+        );
+        // Plain messages don't need a role
+        assert_eq!(
+            context_for(&transport, "I'm hungry").await.role(),
+            &Role::Everyone
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refuses_commands_above_role() -> Result<(), Error> {
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let (tx, mut rx) = mpsc::channel(10);
+        let tx = Mutex::new(tx);
+        let context = context_for(&transport, "!configure enabled off").await;
+        assert!(!check_role(&tx, &context, Role::Moderator).await?);
+        let refusal = match rx.recv().await {
+            Some(AnyMessageEventContent::RoomMessage(MessageEventContent {
+                msgtype: MessageType::Text(TextMessageEventContent { body, .. }),
+                ..
+            })) => body,
+            other => panic!("expected a refusal, got {:?}", other),
+        };
+        assert_eq!(refusal, "Sorry, only a room moderator can ask me that");
+
+        let context = context_for(&transport, "!settings").await;
+        assert!(check_role(&tx, &context, Role::Everyone).await?);
+        transport.set_power_level(ROOM_ID, ALICE, 100).await;
+        let context = context_for(&transport, "!configure enabled off").await;
+        assert!(check_role(&tx, &context, Role::Moderator).await?);
+        assert!(!check_role(&tx, &context, Role::Owner).await?);
+        rx.recv().await;
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+}
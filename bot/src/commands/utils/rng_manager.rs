@@ -1,29 +1,16 @@
 use getset::{Getters, Setters};
 use rand::{RngCore, SeedableRng};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EnabledChance {
     Off,
     Always,
     In(i64),
 }
 
-#[derive(Debug, Clone, Getters, Setters)]
-pub struct RngManager<R: RngCore + SeedableRng + Send> {
-    #[getset(get, set = "pub")]
-    dadded_chance: EnabledChance,
-    #[getset(get, set = "pub")]
-    love_me_chance: EnabledChance,
-    #[getset(get, set = "pub")]
-    rng: R,
-}
-
-impl<R> RngManager<R>
-where
-    R: RngCore + SeedableRng + Send,
-{
-    pub fn new(dadded_chance: Option<i64>, love_me_chance: Option<i64>, rng: R) -> Self {
-        let dadded_chance = match dadded_chance {
+impl EnabledChance {
+    pub fn dadded(chance: Option<i64>) -> Self {
+        match chance {
             None => EnabledChance::Always,
             Some(c) => {
                 if c > 1 {
@@ -32,15 +19,38 @@ where
                     EnabledChance::Always
                 }
             }
-        };
-        let love_me_chance = match love_me_chance {
+        }
+    }
+
+    pub fn love_me(chance: Option<i64>) -> Self {
+        match chance {
             None => EnabledChance::Off,
             Some(c) => match c {
                 0 => EnabledChance::Off,
                 1 => EnabledChance::Always,
                 c => EnabledChance::In(c),
             },
-        };
+        }
+    }
+}
+
+#[derive(Debug, Clone, Getters, Setters)]
+pub struct RngManager<R: RngCore + SeedableRng + Send> {
+    #[getset(get = "pub", set = "pub")]
+    dadded_chance: EnabledChance,
+    #[getset(get = "pub", set = "pub")]
+    love_me_chance: EnabledChance,
+    #[getset(get, set = "pub")]
+    rng: R,
+}
+
+impl<R> RngManager<R>
+where
+    R: RngCore + SeedableRng + Send,
+{
+    pub fn new(dadded_chance: Option<i64>, love_me_chance: Option<i64>, rng: R) -> Self {
+        let dadded_chance = EnabledChance::dadded(dadded_chance);
+        let love_me_chance = EnabledChance::love_me(love_me_chance);
 
         Self {
             dadded_chance,
@@ -49,18 +59,43 @@ where
         }
     }
 
+    pub fn set_chances(&mut self, dadded_chance: Option<i64>, love_me_chance: Option<i64>) {
+        self.dadded_chance = EnabledChance::dadded(dadded_chance);
+        self.love_me_chance = EnabledChance::love_me(love_me_chance);
+    }
+
     pub fn should_dad(&mut self) -> bool {
-        match self.dadded_chance {
-            EnabledChance::In(c) => self.get_n_in_chance(c),
+        let chance = self.dadded_chance.clone();
+        self.should_dad_in(&chance)
+    }
+
+    pub fn should_dad_in(&mut self, chance: &EnabledChance) -> bool {
+        match chance {
+            EnabledChance::In(c) => self.get_n_in_chance(*c),
             _ => true,
         }
     }
 
     pub fn should_love_you(&mut self) -> bool {
-        match self.love_me_chance {
+        let chance = self.love_me_chance.clone();
+        self.should_love_you_in(&chance)
+    }
+
+    pub fn should_love_you_in(&mut self, chance: &EnabledChance) -> bool {
+        match chance {
             EnabledChance::Off => false,
             EnabledChance::Always => true,
-            EnabledChance::In(c) => self.get_n_in_chance(c),
+            EnabledChance::In(c) => self.get_n_in_chance(*c),
+        }
+    }
+
+    pub fn choose<'t, V>(&mut self, items: &'t [V]) -> Option<&'t V> {
+        if items.is_empty() {
+            None
+        } else {
+            let roll = self.rng.next_u64();
+            let idx = (roll % items.len() as u64) as usize;
+            items.get(idx)
         }
     }
 
@@ -154,4 +189,38 @@ mod tests {
         assert_eq!(should_love_you, false);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_should_dad_in_overrides_chance() -> Result<(), Error> {
+        let mock_rng = SeedableStepRng::new(10, 1);
+        let mut mgr = RngManager::new(None, None, mock_rng);
+        let chance = EnabledChance::dadded(Some(10));
+        assert_eq!(mgr.should_dad_in(&chance), true);
+        assert_eq!(mgr.should_dad_in(&chance), false);
+        assert_eq!(mgr.should_dad(), true);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_should_love_you_in_overrides_chance() -> Result<(), Error> {
+        let mock_rng = SeedableStepRng::new(10, 1);
+        let mut mgr = RngManager::new(None, None, mock_rng);
+        let chance = EnabledChance::love_me(Some(1));
+        assert_eq!(mgr.should_love_you_in(&chance), true);
+        assert_eq!(mgr.should_love_you(), false);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_choose() -> Result<(), Error> {
+        let mock_rng = SeedableStepRng::new(4, 1);
+        let mut mgr = RngManager::new(None, None, mock_rng);
+        let items = vec!["first", "second", "third"];
+        assert_eq!(mgr.choose(&items), Some(&"second"));
+        assert_eq!(mgr.choose(&items), Some(&"third"));
+        assert_eq!(mgr.choose(&items), Some(&"first"));
+        let empty: Vec<&str> = vec![];
+        assert_eq!(mgr.choose(&empty), None);
+        Ok(())
+    }
 }
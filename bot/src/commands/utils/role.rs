@@ -0,0 +1,55 @@
+/// Who is allowed to run a command, each role can do everything the roles before it can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Everyone,
+    Moderator,
+    Owner,
+}
+
+impl Role {
+    pub fn new(is_owner: bool, power_level: i64, moderator_power_level: i64) -> Self {
+        if is_owner {
+            Role::Owner
+        } else if power_level >= moderator_power_level {
+            Role::Moderator
+        } else {
+            Role::Everyone
+        }
+    }
+
+    pub fn allows(&self, required: Role) -> bool {
+        *self >= required
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Role::Everyone => "anyone",
+            Role::Moderator => "a room moderator",
+            Role::Owner => "one of my owners",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    #[tokio::test]
+    async fn test_role_from_power_level() -> Result<(), Error> {
+        assert_eq!(Role::new(false, 0, 50), Role::Everyone);
+        assert_eq!(Role::new(false, 50, 50), Role::Moderator);
+        assert_eq!(Role::new(false, 100, 50), Role::Moderator);
+        assert_eq!(Role::new(true, 0, 50), Role::Owner);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_role_allows() -> Result<(), Error> {
+        assert!(Role::Owner.allows(Role::Moderator));
+        assert!(Role::Moderator.allows(Role::Everyone));
+        assert!(!Role::Moderator.allows(Role::Owner));
+        assert!(!Role::Everyone.allows(Role::Moderator));
+        Ok(())
+    }
+}
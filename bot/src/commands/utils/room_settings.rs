@@ -0,0 +1,121 @@
+use crate::commands::utils::{DadMode, EnabledChance};
+use crate::config::Config;
+use crate::errors::Error;
+use chrono::Duration;
+use db::room_settings;
+use db::sea_orm::*;
+use db::utils as dbUtils;
+use getset::Getters;
+
+/// The settings of one room, anything the room hasn't set comes from the config
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct RoomSettings {
+    #[getset(get = "pub")]
+    enabled: bool,
+    // `None` uses the chance the RngManager was configured with
+    #[getset(get = "pub")]
+    dadded_chance: Option<EnabledChance>,
+    #[getset(get = "pub")]
+    love_me_chance: Option<EnabledChance>,
+    #[getset(get = "pub")]
+    locale: Option<String>,
+    #[getset(get = "pub")]
+    cooldown: Option<Duration>,
+    #[getset(get = "pub")]
+    dad_mode: DadMode,
+    // Only comes from the config
+    #[getset(get = "pub")]
+    user_cooldown: Option<Duration>,
+}
+
+impl RoomSettings {
+    pub fn new(config: &Config, room_id: &str, model: Option<room_settings::Model>) -> Self {
+        let (enabled, dadded_chance, love_me_chance, locale, cooldown, dad_mode) = match model {
+            Some(model) => (
+                model.enabled,
+                model.dadded_chance,
+                model.love_me_chance,
+                model.locale,
+                model.cooldown,
+                model.dad_mode,
+            ),
+            None => (true, None, None, None, None, None),
+        };
+        let cooldown = cooldown.or(config.cooldown).filter(|c| *c > 0);
+        let user_cooldown = config.user_cooldown.filter(|c| *c > 0);
+        Self {
+            enabled,
+            dadded_chance: dadded_chance.map(|c| EnabledChance::dadded(Some(c))),
+            love_me_chance: love_me_chance.map(|c| EnabledChance::love_me(Some(c))),
+            locale: locale.or_else(|| config.get_room_locale(room_id)),
+            cooldown: cooldown.map(Duration::seconds),
+            dad_mode: dad_mode
+                .as_deref()
+                .and_then(DadMode::parse)
+                .unwrap_or_else(|| config.get_dad_mode()),
+            user_cooldown: user_cooldown.map(Duration::seconds),
+        }
+    }
+
+    pub async fn load(db: &DbConn, config: &Config, room_id: &str) -> Result<Self, Error> {
+        let model = dbUtils::room_settings::get_room_settings(db, room_id).await?;
+        Ok(Self::new(config, room_id, model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::{create_inmemory_db, load_example_config};
+    use db::utils::room_settings::RoomSetting;
+
+    fn test_config() -> Config<'static> {
+        let mut config = load_example_config();
+        config.cooldown = Some(60);
+        config.user_cooldown = Some(600);
+        config
+    }
+
+    #[tokio::test]
+    async fn test_room_settings_default_to_config() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let config = test_config();
+        let room_id = "!someroom:matrix.org";
+
+        let settings = RoomSettings::load(&db, &config, room_id).await?;
+
+        assert_eq!(*settings.enabled(), true);
+        assert_eq!(*settings.dadded_chance(), None);
+        assert_eq!(*settings.love_me_chance(), None);
+        assert_eq!(*settings.locale(), config.get_room_locale(room_id));
+        assert_eq!(*settings.cooldown(), Some(Duration::minutes(1)));
+        assert_eq!(*settings.dad_mode(), config.get_dad_mode());
+        assert_eq!(*settings.user_cooldown(), Some(Duration::minutes(10)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_room_settings_override_config() -> Result<(), Error> {
+        let db = create_inmemory_db().await?;
+        let config = test_config();
+        let room_id = "!someroom:matrix.org";
+
+        let set = |setting| dbUtils::room_settings::set_room_setting(&db, room_id, setting);
+        set(RoomSetting::Enabled(false)).await?;
+        set(RoomSetting::DaddedChance(Some(10))).await?;
+        set(RoomSetting::LoveMeChance(Some(0))).await?;
+        set(RoomSetting::Locale(Some(String::from("es")))).await?;
+        set(RoomSetting::Cooldown(Some(0))).await?;
+        set(RoomSetting::DadMode(Some(String::from("both")))).await?;
+        let settings = RoomSettings::load(&db, &config, room_id).await?;
+
+        assert_eq!(*settings.enabled(), false);
+        assert_eq!(*settings.dadded_chance(), Some(EnabledChance::In(10)));
+        assert_eq!(*settings.love_me_chance(), Some(EnabledChance::Off));
+        assert_eq!(*settings.locale(), Some(String::from("es")));
+        assert_eq!(*settings.cooldown(), None);
+        assert_eq!(*settings.dad_mode(), DadMode::Both);
+        assert_eq!(*settings.user_cooldown(), Some(Duration::minutes(10)));
+        Ok(())
+    }
+}
@@ -1,7 +1,36 @@
+use crate::commands::utils::DadMode;
 use chrono::Duration;
 use mrsbfh::config::ConfigDerive;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::HashMap;
+
+pub const DEFAULT_TEMPLATE: &str = "Hi {dad_text}! I'm Dad!";
+pub const DEFAULT_LOVE_TEMPLATE: &str = "Hi {dad_text}! I'm Dad and I love you!";
+pub const DEFAULT_MODERATOR_POWER_LEVEL: i64 = 50;
+pub const DEFAULT_MAX_ECHO_LENGTH: usize = 100;
+pub const DEFAULT_REACTION: &str = "👋";
+pub const DEFAULT_BACKLOG_GRACE: i64 = 60;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TriggerConfig<'a> {
+    pub regex: Cow<'a, str>,
+    // Falls back to the top level templates
+    pub templates: Option<Vec<Cow<'a, str>>>,
+    pub love_templates: Option<Vec<Cow<'a, str>>>,
+    // For 1 in Chance, falls back to dadded_chance
+    pub chance: Option<i64>,
+}
+
+impl<'a> TriggerConfig<'a> {
+    pub fn get_templates(&self, config: &Config) -> Vec<String> {
+        templates_or(&self.templates, config.get_templates())
+    }
+
+    pub fn get_love_templates(&self, config: &Config) -> Vec<String> {
+        templates_or(&self.love_templates, config.get_love_templates())
+    }
+}
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, ConfigDerive)]
 pub struct Config<'a> {
@@ -20,10 +49,141 @@ pub struct Config<'a> {
     pub dadded_chance: Option<i64>,
     // Say I love you 1 in Chance during a dadded
     pub love_me_chance: Option<i64>,
+    // Dadded reply templates, `{name}` is filled with the regex group `name`, `{sender}` or `{room}`
+    pub templates: Option<Vec<Cow<'a, str>>>,
+    // Dadded reply templates used when saying I love you
+    pub love_templates: Option<Vec<Cow<'a, str>>>,
+    // Extra triggers, tried in order before dadded_regex
+    pub triggers: Option<Vec<TriggerConfig<'a>>>,
+    // Built-in locale packs, tried in order after dadded_regex
+    pub locales: Option<Vec<Cow<'a, str>>>,
+    // Room ID to the only locale pack that should be used in that room
+    pub room_locales: Option<HashMap<Cow<'a, str>, Cow<'a, str>>>,
+    // Seconds to wait after a dadded before dadding the same room again
+    pub cooldown: Option<i64>,
+    // Seconds to wait after dadding someone before dadding them again in the same room, rooms can't override it
+    pub user_cooldown: Option<i64>,
+    // MXIDs that may run every command in every room
+    pub owners: Option<Vec<Cow<'a, str>>>,
+    // Power level needed to run moderator commands, defaults to 50
+    pub moderator_power_level: Option<i64>,
+    // Send dadded replies as rich replies quoting the trigger, replies in threads always stay in the thread
+    pub rich_replies: Option<bool>,
+    // Edit the dadded reply when its trigger is edited, edits are ignored otherwise
+    pub update_on_edit: Option<bool>,
+    // Let m.notice messages, usually sent by other bots, trigger replies
+    pub reply_to_notices: Option<bool>,
+    // Longest text, in characters, echoed back from a message or name, defaults to 100
+    pub max_echo_length: Option<usize>,
+    // Start dadded replies with a pill mentioning who was dadded
+    pub mention_sender: Option<bool>,
+    // Dad with a reply, a reaction on the trigger or both, rooms can override it, defaults to reply
+    pub dad_mode: Option<Cow<'a, str>>,
+    // Reaction key used when dadding with a reaction
+    pub reaction: Option<Cow<'a, str>>,
+    // Seconds before startup a message may have been sent and still be handled, defaults to 60
+    pub backlog_grace: Option<i64>,
+    // MXIDs that never get answered, as globs like `@*bot:example.org` or regexes like `/^@irc_/`
+    pub ignored_users: Option<Vec<Cow<'a, str>>>,
+    // Homeservers whose users never get answered
+    pub ignored_servers: Option<Vec<Cow<'a, str>>>,
+    // Room IDs where the bot stays quiet
+    pub ignored_rooms: Option<Vec<Cow<'a, str>>>,
+    // MXIDs allowed to invite the bot, owners always can and everyone can if this and
+    // invite_allowed_servers are both unset
+    pub invite_allowed_users: Option<Vec<Cow<'a, str>>>,
+    // Homeservers whose users may invite the bot
+    pub invite_allowed_servers: Option<Vec<Cow<'a, str>>>,
+    // Invites to rooms with more members than this are rejected
+    pub invite_max_members: Option<u64>,
+    // Accept invites to direct messages, defaults to true
+    pub accept_direct_invites: Option<bool>,
 }
 
 impl<'a> Config<'a> {
     pub fn get_epoch_length(&self) -> Duration {
         Duration::minutes(self.epoch_length)
     }
+
+    pub fn get_templates(&self) -> Vec<String> {
+        templates_or(&self.templates, vec![DEFAULT_TEMPLATE.to_string()])
+    }
+
+    pub fn get_love_templates(&self) -> Vec<String> {
+        templates_or(
+            &self.love_templates,
+            vec![DEFAULT_LOVE_TEMPLATE.to_string()],
+        )
+    }
+
+    pub fn is_owner(&self, mxid: &str) -> bool {
+        self.owners
+            .iter()
+            .flatten()
+            .any(|owner| owner.eq_ignore_ascii_case(mxid))
+    }
+
+    pub fn get_moderator_power_level(&self) -> i64 {
+        self.moderator_power_level
+            .unwrap_or(DEFAULT_MODERATOR_POWER_LEVEL)
+    }
+
+    pub fn get_rich_replies(&self) -> bool {
+        self.rich_replies.unwrap_or(false)
+    }
+
+    pub fn get_update_on_edit(&self) -> bool {
+        self.update_on_edit.unwrap_or(false)
+    }
+
+    pub fn get_reply_to_notices(&self) -> bool {
+        self.reply_to_notices.unwrap_or(false)
+    }
+
+    pub fn get_max_echo_length(&self) -> usize {
+        self.max_echo_length.unwrap_or(DEFAULT_MAX_ECHO_LENGTH)
+    }
+
+    pub fn get_mention_sender(&self) -> bool {
+        self.mention_sender.unwrap_or(false)
+    }
+
+    /// Unknown modes fall back to replies, `find_problems` reports them
+    pub fn get_dad_mode(&self) -> DadMode {
+        self.dad_mode
+            .as_deref()
+            .and_then(DadMode::parse)
+            .unwrap_or_default()
+    }
+
+    pub fn get_reaction(&self) -> String {
+        match &self.reaction {
+            Some(reaction) if !reaction.is_empty() => reaction.to_string(),
+            _ => DEFAULT_REACTION.to_string(),
+        }
+    }
+
+    pub fn get_backlog_grace(&self) -> Duration {
+        Duration::seconds(self.backlog_grace.unwrap_or(DEFAULT_BACKLOG_GRACE))
+    }
+
+    pub fn get_accept_direct_invites(&self) -> bool {
+        self.accept_direct_invites.unwrap_or(true)
+    }
+
+    pub fn get_room_locale(&self, room_id: &str) -> Option<String> {
+        self.room_locales
+            .as_ref()
+            .and_then(|locales| locales.get(room_id))
+            .map(|locale| locale.to_string())
+    }
+}
+
+fn templates_or(templates: &Option<Vec<Cow<'_, str>>>, default: Vec<String>) -> Vec<String> {
+    match templates {
+        Some(templates) if !templates.is_empty() => {
+            templates.iter().map(|t| t.to_string()).collect()
+        }
+        _ => default,
+    }
 }
@@ -1,2 +1,6 @@
 mod matrix;
-pub use matrix::Config;
+mod validate;
+pub use matrix::{
+    Config, TriggerConfig, DEFAULT_LOVE_TEMPLATE, DEFAULT_MAX_ECHO_LENGTH, DEFAULT_TEMPLATE,
+};
+pub use validate::find_problems;
@@ -0,0 +1,1299 @@
+use crate::commands::utils::{Cooldown, DaddedManager, EnabledChance, RngManager, RoomSettings};
+use crate::config::Config;
+use crate::errors::Error;
+use crate::transport::{IncomingMessage, Mention, MessageKind, OutgoingMessage, Transport};
+use crate::triggers::Triggers;
+use chrono::Local;
+use db::sea_orm::DbConn;
+use db::utils::dad_events::{self, NewDadEvent};
+use rand::RngCore;
+use rand::SeedableRng;
+use regex::Regex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+pub use self::sanitize::sanitize;
+mod sanitize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DadReply {
+    pub text: String,
+    pub dad_text: String,
+    pub loved: bool,
+    pub template: String,
+}
+
+/// Where a message left the dadding pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum DadOutcome {
+    Disabled,
+    OptedOut,
+    NoTrigger,
+    CoolingDown(Cooldown),
+    Unlucky,
+    Dadded(DadReply),
+}
+
+/// The outcome of a message along with what was decided on the way there
+#[derive(Debug, Clone, PartialEq)]
+pub struct DadAttempt {
+    pub outcome: DadOutcome,
+    pub trigger: Option<String>,
+    pub captures: HashMap<String, String>,
+    pub dadded_chance: Option<EnabledChance>,
+    pub should_dad: Option<bool>,
+    pub should_love: Option<bool>,
+}
+
+impl DadAttempt {
+    fn new(outcome: DadOutcome) -> Self {
+        Self {
+            outcome,
+            trigger: None,
+            captures: HashMap::new(),
+            dadded_chance: None,
+            should_dad: None,
+            should_love: None,
+        }
+    }
+
+    pub fn reply(self) -> Option<DadReply> {
+        match self.outcome {
+            DadOutcome::Dadded(reply) => Some(reply),
+            _ => None,
+        }
+    }
+}
+
+fn get_template_values(dadded_regex: &Regex, msg: &str) -> Option<HashMap<String, String>> {
+    let dad_caps = dadded_regex.captures(msg)?;
+    let mut values = HashMap::new();
+    for name in dadded_regex.capture_names().flatten() {
+        if let Some(named) = dad_caps.name(name) {
+            let named_string = named.as_str().to_string();
+            debug!("Found '{}' named group: {}", name, &named_string);
+            values.insert(name.to_string(), named_string);
+        }
+    }
+    // Patterns without named groups use the first two groups for 'im' and 'dad_text'
+    for (idx, name) in [(1, "im"), (2, "dad_text")] {
+        if !values.contains_key(name) {
+            if let Some(group) = dad_caps.get(idx) {
+                let group_string = group.as_str().to_string();
+                debug!("Found group {} ('{}'): {}", idx, name, &group_string);
+                values.insert(name.to_string(), group_string);
+            }
+        }
+    }
+    Some(values)
+}
+
+fn render_template(template: &str, values: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let placeholder = &rest[open..];
+        match placeholder.find('}') {
+            Some(close) => {
+                match values.get(&placeholder[1..close]) {
+                    Some(value) => rendered.push_str(value),
+                    None => rendered.push_str(&placeholder[..=close]),
+                }
+                rest = &placeholder[close + 1..];
+            }
+            None => {
+                rest = placeholder;
+                break;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn create_dadded_text(
+    dadded_regex: &Regex,
+    msg: &str,
+    template: &str,
+    mut values: HashMap<String, String>,
+    should_love: bool,
+    max_length: usize,
+) -> Option<DadReply> {
+    values.extend(get_template_values(dadded_regex, msg)?);
+    let to_be_dadded = values.get("dad_text")?.clone();
+    // Everything filled in comes from users, only the template may format the reply
+    let values = values
+        .into_iter()
+        .map(|(name, value)| (name, sanitize(&value, max_length)))
+        .collect();
+    let dadded_string = render_template(template, &values);
+    Some(DadReply {
+        text: dadded_string,
+        dad_text: to_be_dadded,
+        loved: should_love,
+        template: template.to_string(),
+    })
+}
+
+// Emoted replies read as `* Dad says: Hi tired! I'm Dad!`
+const EMOTE_PREFIX: &str = "says: ";
+
+/// `/me is tired` means `I am tired`, so emotes are put in the first person before matching
+fn get_first_person_emote(body: &str) -> String {
+    let body = body.trim_start();
+    match body.get(..3) {
+        Some(verb) if verb.eq_ignore_ascii_case("is ") => format!("I am {}", &body[3..]),
+        _ => body.to_string(),
+    }
+}
+
+/// The text of a message that triggers are matched against
+fn get_match_body(message: &IncomingMessage) -> String {
+    match message.kind {
+        MessageKind::Emote => get_first_person_emote(&message.body),
+        _ => message.body.clone(),
+    }
+}
+
+async fn get_room_values<Tr>(transport: &Tr, room_id: &str, sender: &str) -> HashMap<String, String>
+where
+    Tr: Transport + ?Sized,
+{
+    let sender = match transport.display_name(room_id, sender).await {
+        Some(name) => name,
+        None => sender.to_string(),
+    };
+    let room_name = match transport.room_name(room_id).await {
+        Some(name) => name,
+        None => room_id.to_string(),
+    };
+    let mut values = HashMap::new();
+    values.insert(String::from("sender"), sender);
+    values.insert(String::from("room"), room_name);
+    values
+}
+
+async fn get_mention<Tr>(transport: &Tr, room_id: &str, sender: &str, max_length: usize) -> Mention
+where
+    Tr: Transport + ?Sized,
+{
+    let display_name = match transport.display_name(room_id, sender).await {
+        Some(name) => name,
+        None => sender.to_string(),
+    };
+    Mention {
+        user_id: sender.to_string(),
+        display_name: sanitize(&display_name, max_length),
+    }
+}
+
+/// Everything needed to decide whether to dad a message, without knowing where it came from
+pub struct Dadder<T>
+where
+    T: RngCore + SeedableRng + Send,
+{
+    config: Arc<Mutex<Config<'static>>>,
+    db: Arc<Mutex<DbConn>>,
+    dad_handler: Arc<Mutex<DaddedManager>>,
+    rng_handler: Arc<Mutex<RngManager<T>>>,
+    triggers: Arc<Mutex<Triggers>>,
+}
+
+impl<T> Dadder<T>
+where
+    T: RngCore + SeedableRng + Send,
+{
+    pub fn new(
+        config: Arc<Mutex<Config<'static>>>,
+        db: Arc<Mutex<DbConn>>,
+        dad_handler: Arc<Mutex<DaddedManager>>,
+        rng_handler: Arc<Mutex<RngManager<T>>>,
+        triggers: Arc<Mutex<Triggers>>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            dad_handler,
+            rng_handler,
+            triggers,
+        }
+    }
+
+    pub async fn update_epoch(&self) -> Result<(), Error> {
+        let dad_mgr = &mut *self.dad_handler.lock().await;
+        let config = &*self.config.lock().await;
+        let db = &*self.db.lock().await;
+        let epoch_duration = config.get_epoch_length();
+        let epoch_changed = dad_mgr
+            .check_for_epoch_update(db, Local::now(), epoch_duration)
+            .await?;
+        if let true = epoch_changed {
+            let rng_mgr = &mut *self.rng_handler.lock().await;
+            let new_rng = T::from_entropy();
+            rng_mgr.set_rng(new_rng);
+        }
+        Ok(())
+    }
+
+    async fn get_room_settings(&self, room_id: &str) -> Result<RoomSettings, Error> {
+        let config = &*self.config.lock().await;
+        let db = &*self.db.lock().await;
+        RoomSettings::load(db, config, room_id).await
+    }
+
+    async fn is_sender_opted_out(&self, room_id: &str, sender: &str) -> Result<bool, Error> {
+        let db = &*self.db.lock().await;
+        let opted_out = db::utils::opt_outs::is_opted_out(db, sender, room_id).await?;
+        Ok(opted_out)
+    }
+
+    /// Runs a message through the room settings, triggers, cooldowns and dice,
+    /// `room_values` is only awaited once it's certain there will be a reply
+    pub async fn attempt<F>(
+        &self,
+        room_id: &str,
+        sender: &str,
+        msg: &str,
+        room_values: F,
+    ) -> Result<DadAttempt, Error>
+    where
+        F: Future<Output = HashMap<String, String>>,
+    {
+        let settings = self.get_room_settings(room_id).await?;
+        let max_length = self.config.lock().await.get_max_echo_length();
+        if !*settings.enabled() {
+            debug!("Dadding is disabled in {}", room_id);
+            return Ok(DadAttempt::new(DadOutcome::Disabled));
+        }
+        if self.is_sender_opted_out(room_id, sender).await? {
+            debug!("{} opted out of dadding in {}", sender, room_id);
+            return Ok(DadAttempt::new(DadOutcome::OptedOut));
+        }
+        let triggers = &*self.triggers.lock().await;
+        let trigger = match triggers.find(msg, settings.locale().as_deref()) {
+            Some(trigger) => trigger,
+            None => return Ok(DadAttempt::new(DadOutcome::NoTrigger)),
+        };
+        let mut attempt = DadAttempt::new(DadOutcome::Unlucky);
+        attempt.trigger = Some(trigger.regex().to_string());
+        attempt.captures = get_template_values(trigger.regex(), msg).unwrap_or_default();
+        let cooldown = self.dad_handler.lock().await.check_cooldown(
+            room_id,
+            sender,
+            Local::now(),
+            *settings.cooldown(),
+            *settings.user_cooldown(),
+        );
+        if let Some(cooldown) = cooldown {
+            attempt.outcome = DadOutcome::CoolingDown(cooldown);
+            return Ok(attempt);
+        }
+        let rng = &mut *self.rng_handler.lock().await;
+        let dadded_chance = trigger
+            .chance()
+            .clone()
+            .or_else(|| settings.dadded_chance().clone());
+        let should_dad = match &dadded_chance {
+            Some(chance) => rng.should_dad_in(chance),
+            None => rng.should_dad(),
+        };
+        attempt.dadded_chance = dadded_chance.or_else(|| Some(rng.dadded_chance().clone()));
+        attempt.should_dad = Some(should_dad);
+        if should_dad {
+            let values = room_values.await;
+            let should_love = match settings.love_me_chance() {
+                Some(chance) => rng.should_love_you_in(chance),
+                None => rng.should_love_you(),
+            };
+            attempt.should_love = Some(should_love);
+            let reply = rng
+                .choose(trigger.get_templates(should_love))
+                .and_then(|template| {
+                    create_dadded_text(
+                        trigger.regex(),
+                        msg,
+                        template,
+                        values,
+                        should_love,
+                        max_length,
+                    )
+                });
+            if let Some(reply) = reply {
+                attempt.outcome = DadOutcome::Dadded(reply);
+            }
+        }
+        Ok(attempt)
+    }
+
+    /// Counts a sent reply towards the room, the victim and the event log
+    pub async fn record(&self, dad_event: NewDadEvent) -> Result<(), Error> {
+        let mgr = &mut *self.dad_handler.lock().await;
+        let db = &*self.db.lock().await;
+        mgr.mark_dadded(&dad_event.room_id, &dad_event.sender, Local::now());
+        mgr.increment_dadded(db, &dad_event.room_id).await?;
+        mgr.increment_victim(db, &dad_event.room_id, &dad_event.sender)
+            .await?;
+        mgr.log_dad_event(db, Local::now(), dad_event).await?;
+        Ok(())
+    }
+
+    /// Counts a sent reaction, which has no reply to log or to edit later. When a reply went out
+    /// with it the reply already counted the victim and the cooldown
+    pub async fn record_reaction(
+        &self,
+        room_id: &str,
+        sender: &str,
+        with_reply: bool,
+    ) -> Result<(), Error> {
+        let mgr = &mut *self.dad_handler.lock().await;
+        let db = &*self.db.lock().await;
+        mgr.increment_reaction_dadded(db, room_id).await?;
+        if !with_reply {
+            mgr.mark_dadded(room_id, sender, Local::now());
+            mgr.increment_victim(db, room_id, sender).await?;
+        }
+        Ok(())
+    }
+
+    /// Edits the reply to an edited trigger when the dadded text changed, edits of messages
+    /// that weren't dadded are never dadded themselves
+    async fn update_edited_reply<Tr>(
+        &self,
+        transport: &Tr,
+        message: &IncomingMessage,
+        replaces: &str,
+    ) -> Result<(), Error>
+    where
+        Tr: Transport + ?Sized,
+    {
+        let dad_event = {
+            let db = &*self.db.lock().await;
+            dad_events::get_dad_event_by_trigger(db, &message.room_id, replaces).await?
+        };
+        let dad_event = match dad_event {
+            Some(dad_event) => dad_event,
+            None => {
+                debug!("{} wasn't dadded, ignoring its edit", replaces);
+                return Ok(());
+            }
+        };
+        let settings = self.get_room_settings(&message.room_id).await?;
+        if !*settings.enabled() {
+            debug!(
+                "Dadding is disabled in {}, ignoring the edit",
+                message.room_id
+            );
+            return Ok(());
+        }
+        if self
+            .is_sender_opted_out(&message.room_id, &message.sender)
+            .await?
+        {
+            debug!(
+                "{} opted out of dadding in {}, ignoring the edit",
+                message.sender, message.room_id
+            );
+            return Ok(());
+        }
+        let body = get_match_body(message);
+        let max_length = self.config.lock().await.get_max_echo_length();
+        let values = get_room_values(transport, &message.room_id, &message.sender).await;
+        // Cloned so the triggers aren't held while waiting for the RNG
+        let trigger = self
+            .triggers
+            .lock()
+            .await
+            .find(&body, settings.locale().as_deref())
+            .cloned();
+        let trigger = match trigger {
+            Some(trigger) => trigger,
+            None => {
+                debug!("Edit of {} no longer matches a trigger", replaces);
+                return Ok(());
+            }
+        };
+        let dad_text = get_template_values(trigger.regex(), &body)
+            .and_then(|mut captures| captures.remove("dad_text"));
+        if dad_text.as_deref() == Some(dad_event.dad_text.as_str()) {
+            debug!("Edit of {} dadded the same text", replaces);
+            return Ok(());
+        }
+        // The edited reply keeps its template, unless it's unknown or the edit matched another trigger
+        let templates = trigger.get_templates(dad_event.loved);
+        let template = if templates.contains(&dad_event.template) {
+            Some(dad_event.template.clone())
+        } else {
+            self.rng_handler.lock().await.choose(templates).cloned()
+        };
+        let reply = template.and_then(|template| {
+            create_dadded_text(
+                trigger.regex(),
+                &body,
+                &template,
+                values,
+                dad_event.loved,
+                max_length,
+            )
+        });
+        if let Some(reply) = reply {
+            let outgoing = self.build_reply(transport, message, &reply).await;
+            info!(
+                "Editing Dadded {}: {}",
+                dad_event.reply_event_id, &outgoing.markdown
+            );
+            transport
+                .edit_message(&message.room_id, &dad_event.reply_event_id, &outgoing)
+                .await?;
+            let db = &*self.db.lock().await;
+            dad_events::set_dad_text(db, dad_event, &reply.dad_text, &reply.template).await?;
+        }
+        Ok(())
+    }
+
+    /// The message answering `message` with `reply`, emotes are answered with an emote
+    async fn build_reply<Tr>(
+        &self,
+        transport: &Tr,
+        message: &IncomingMessage,
+        reply: &DadReply,
+    ) -> OutgoingMessage
+    where
+        Tr: Transport + ?Sized,
+    {
+        let (kind, markdown) = match message.kind {
+            MessageKind::Emote => (
+                MessageKind::Emote,
+                format!("{}{}", EMOTE_PREFIX, reply.text),
+            ),
+            _ => (MessageKind::Text, reply.text.clone()),
+        };
+        let (rich_replies, mention_sender, max_length) = {
+            let config = &*self.config.lock().await;
+            (
+                config.get_rich_replies(),
+                config.get_mention_sender(),
+                config.get_max_echo_length(),
+            )
+        };
+        let mention = if mention_sender {
+            Some(get_mention(transport, &message.room_id, &message.sender, max_length).await)
+        } else {
+            None
+        };
+        OutgoingMessage {
+            kind,
+            markdown,
+            reply_to: message.reply_to(rich_replies),
+            mention,
+        }
+    }
+
+    /// Sends the dadded text for `message` and records it, failures are only logged
+    async fn send_reply<Tr>(&self, transport: &Tr, message: &IncomingMessage, reply: &DadReply)
+    where
+        Tr: Transport + ?Sized,
+    {
+        let outgoing = self.build_reply(transport, message, reply).await;
+        info!("Sending Dadded: {}", &outgoing.markdown);
+        let sent = transport.send_message(&message.room_id, &outgoing).await;
+        match sent {
+            Err(e) => error!("{}", e),
+            Ok(reply_event_id) => {
+                // Update DB
+                info!("Recording Dadded...");
+                let dad_event = NewDadEvent {
+                    room_id: message.room_id.clone(),
+                    sender: message.sender.clone(),
+                    trigger_event_id: message.event_id.clone(),
+                    reply_event_id,
+                    dad_text: reply.dad_text.clone(),
+                    loved: reply.loved,
+                    template: reply.template.clone(),
+                };
+                if let Err(e) = self.record(dad_event).await {
+                    error!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Dads a message that came in over `transport`, replying and/or reacting as the room's
+    /// mode says and recording it.
+    /// Returns `None` for messages the bot doesn't look at, like its own
+    pub async fn handle_message<Tr>(
+        &self,
+        transport: &Tr,
+        message: &IncomingMessage,
+    ) -> Result<Option<DadAttempt>, Error>
+    where
+        Tr: Transport + ?Sized,
+    {
+        info!("Ticking manager epoch...");
+        self.update_epoch().await?;
+        if message.sender == transport.own_user_id() {
+            return Ok(None);
+        }
+        // Notices are usually other bots, answering them can start a loop
+        if message.kind == MessageKind::Notice && !self.config.lock().await.get_reply_to_notices() {
+            debug!(
+                "Ignoring notice {} in {}",
+                message.event_id, message.room_id
+            );
+            return Ok(None);
+        }
+        if let Some(replaces) = &message.replaces {
+            if self.config.lock().await.get_update_on_edit() {
+                self.update_edited_reply(transport, message, replaces)
+                    .await?;
+            } else {
+                debug!("Ignoring edit of {} in {}", replaces, message.room_id);
+            }
+            return Ok(None);
+        }
+        let body = get_match_body(message);
+        let attempt = self
+            .attempt(
+                &message.room_id,
+                &message.sender,
+                &body,
+                get_room_values(transport, &message.room_id, &message.sender),
+            )
+            .await?;
+        if let DadOutcome::Dadded(reply) = &attempt.outcome {
+            let mode = *self.get_room_settings(&message.room_id).await?.dad_mode();
+            if mode.replies() {
+                self.send_reply(transport, message, reply).await;
+            }
+            if mode.reacts() {
+                let key = self.config.lock().await.get_reaction();
+                info!("Reacting to {} with {}", message.event_id, key);
+                let sent = transport
+                    .send_reaction(&message.room_id, &message.event_id, &key)
+                    .await;
+                match sent {
+                    Err(e) => error!("{}", e),
+                    Ok(_) => {
+                        info!("Recording reaction Dadded...");
+                        if let Err(e) = self
+                            .record_reaction(&message.room_id, &message.sender, mode.replies())
+                            .await
+                        {
+                            error!("{}", e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Some(attempt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DEFAULT_LOVE_TEMPLATE, DEFAULT_MAX_ECHO_LENGTH, DEFAULT_TEMPLATE};
+    use crate::integration_utils::{create_inmemory_db, load_example_config, SeedableStepRng};
+    use crate::transport::{InMemoryTransport, Mention, ReplyTo, SentEvent};
+    use db::utils::epochs;
+    use db::utils::room_settings::RoomSetting;
+    use regex::RegexBuilder;
+    use tokio::sync::OnceCell;
+
+    static TEST_DADDED_RE: OnceCell<Regex> = OnceCell::const_new();
+    static TEST_DADDED_RE_NAMED: OnceCell<Regex> = OnceCell::const_new();
+
+    async fn get_test_regex() -> &'static Regex {
+        TEST_DADDED_RE
+            .get_or_init(move || async {
+                RegexBuilder::new(r"\b((?:i|l)(?:(?:'|`|‛|‘|’|′|‵)?m| am))(?:\s+)([^\.!?]+)")
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap()
+            })
+            .await
+    }
+
+    async fn get_test_named_regex() -> &'static Regex {
+        TEST_DADDED_RE_NAMED
+            .get_or_init(move || async {
+                RegexBuilder::new(
+                    r"\b(?P<im>(?:i|l)(?:(?:'|`|‛|‘|’|′|‵)?m| am))(?:\s+)(?P<dad_text>[^\.!?]+)",
+                )
+                .case_insensitive(true)
+                .build()
+                .unwrap()
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_generate_dad_regex() -> Result<(), Error> {
+        let re = get_test_regex().await;
+        let named_re = get_test_named_regex().await;
+        let chat_msg = String::from("I'm hungry.");
+        let resp = create_dadded_text(
+            re,
+            &chat_msg,
+            DEFAULT_TEMPLATE,
+            HashMap::new(),
+            false,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap()
+        .text;
+        let named_resp = create_dadded_text(
+            named_re,
+            &chat_msg,
+            DEFAULT_TEMPLATE,
+            HashMap::new(),
+            false,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap()
+        .text;
+        let expected_resp = String::from("Hi hungry! I'm Dad!");
+        assert_eq!(resp, expected_resp);
+        assert_eq!(named_resp, expected_resp);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_dad_with_love() -> Result<(), Error> {
+        let re = get_test_regex().await;
+        let chat_msg = String::from("I'm hungry.");
+        let resp = create_dadded_text(
+            re,
+            &chat_msg,
+            DEFAULT_LOVE_TEMPLATE,
+            HashMap::new(),
+            true,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap()
+        .text;
+        let expected_resp = String::from("Hi hungry! I'm Dad and I love you!");
+        assert_eq!(resp, expected_resp);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_regex_from_config() -> Result<(), Error> {
+        let regex_str = String::from(
+            r"\b(?P<im>(?:i|l)(?:(?:'|`|‛|‘|’|′|‵)?m| am))(?:\s+)(?P<dad_text>[^\.!?]+)",
+        );
+        let chat_msg = String::from("I'm hungry.");
+        let expected_resp = String::from("Hi hungry! I'm Dad!");
+        let re = RegexBuilder::new(&regex_str)
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+
+        let resp = create_dadded_text(
+            &re,
+            &chat_msg,
+            DEFAULT_TEMPLATE,
+            HashMap::new(),
+            false,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap()
+        .text;
+
+        assert_eq!(resp, expected_resp);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dad_reply_captures_dad_text() -> Result<(), Error> {
+        let re = get_test_named_regex().await;
+        let chat_msg = String::from("Well I am so tired!");
+        let reply = create_dadded_text(
+            re,
+            &chat_msg,
+            DEFAULT_LOVE_TEMPLATE,
+            HashMap::new(),
+            true,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap();
+        let expected_reply = DadReply {
+            text: String::from("Hi so tired! I'm Dad and I love you!"),
+            dad_text: String::from("so tired"),
+            loved: true,
+            template: DEFAULT_LOVE_TEMPLATE.to_string(),
+        };
+        assert_eq!(reply, expected_reply);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_placeholders() -> Result<(), Error> {
+        let re = get_test_named_regex().await;
+        let chat_msg = String::from("I am hungry");
+        let mut values = HashMap::new();
+        values.insert(String::from("sender"), String::from("Alice"));
+        values.insert(String::from("room"), String::from("Kitchen"));
+        let template = "{sender} said '{im} {dad_text}' in {room}, {unknown} stays";
+        let reply = create_dadded_text(
+            re,
+            &chat_msg,
+            template,
+            values,
+            false,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap();
+        assert_eq!(
+            reply.text,
+            String::from("Alice said 'I am hungry' in Kitchen, {unknown} stays")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_unnamed_groups() -> Result<(), Error> {
+        let re = get_test_regex().await;
+        let chat_msg = String::from("I'm hungry.");
+        let template = "{im} {dad_text}? Dad says {dad_text} is fine";
+        let reply = create_dadded_text(
+            re,
+            &chat_msg,
+            template,
+            HashMap::new(),
+            false,
+            DEFAULT_MAX_ECHO_LENGTH,
+        )
+        .unwrap();
+        assert_eq!(
+            reply.text,
+            String::from("I'm hungry? Dad says hungry is fine")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_values_are_sanitized() -> Result<(), Error> {
+        let re = get_test_named_regex().await;
+        let chat_msg = String::from("I'm **@room** [click](http://evil)");
+        let mut values = HashMap::new();
+        values.insert(String::from("sender"), String::from("<b>Mallory</b>"));
+        let reply = create_dadded_text(
+            re,
+            &chat_msg,
+            "**Hi {dad_text}**, {sender}",
+            values,
+            false,
+            10,
+        )
+        .unwrap();
+        assert_eq!(
+            reply.text,
+            String::from("**Hi \\*\\*@\u{2060}room\\*\\* …**, \\<b\\>Mallory…")
+        );
+        assert_eq!(
+            reply.dad_text,
+            String::from("**@room** [click](http://evil)")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_values_are_not_rendered_twice() -> Result<(), Error> {
+        let mut values = HashMap::new();
+        values.insert(String::from("dad_text"), String::from("{sender}"));
+        values.insert(String::from("sender"), String::from("Alice"));
+        let text = render_template("Hi {dad_text}! {sender {nope", &values);
+        assert_eq!(text, String::from("Hi {sender}! {sender {nope"));
+        Ok(())
+    }
+
+    async fn create_dadder(config: Config<'static>) -> Result<Dadder<SeedableStepRng>, Error> {
+        let db = create_inmemory_db().await?;
+        let epoch_length = config.get_epoch_length();
+        let epoch = epochs::get_or_create_epoch(&db, &Local::now(), epoch_length).await?;
+        let next_epoch = epochs::get_next_epoch_bound(&db, epoch.id, epoch_length).await?;
+        let triggers = Triggers::from_config(&config)?;
+        let rng = RngManager::new(
+            config.dadded_chance,
+            config.love_me_chance,
+            SeedableStepRng::new(0, 1),
+        );
+        Ok(Dadder::new(
+            Arc::new(Mutex::new(config)),
+            Arc::new(Mutex::new(db)),
+            Arc::new(Mutex::new(DaddedManager::new(epoch.id, next_epoch))),
+            Arc::new(Mutex::new(rng)),
+            Arc::new(Mutex::new(triggers)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_dadder_pipeline() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        let dadder = create_dadder(config).await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+        let attempt = |sender: &'static str, msg: &'static str| {
+            dadder.attempt(room_id, sender, msg, async { HashMap::new() })
+        };
+
+        assert_eq!(
+            attempt(alice, "Hello there").await?.outcome,
+            DadOutcome::NoTrigger
+        );
+
+        let dadded = attempt(alice, "I'm hungry").await?;
+        assert_eq!(
+            dadded.captures.get("dad_text"),
+            Some(&String::from("hungry"))
+        );
+        assert_eq!(dadded.dadded_chance, Some(EnabledChance::Always));
+        assert_eq!(dadded.should_dad, Some(true));
+        assert_eq!(dadded.should_love, Some(false));
+        let reply = dadded.reply().unwrap();
+        assert_eq!(reply.text, String::from("Hi hungry! I'm Dad!"));
+
+        dadder
+            .record(NewDadEvent {
+                room_id: room_id.to_string(),
+                sender: alice.to_string(),
+                trigger_event_id: String::from("$trigger"),
+                reply_event_id: String::from("$reply"),
+                dad_text: reply.dad_text,
+                loved: reply.loved,
+                template: reply.template,
+            })
+            .await?;
+        let dad = dadder
+            .dad_handler
+            .lock()
+            .await
+            .get_current_dad(&*dadder.db.lock().await, room_id)
+            .await?;
+        assert_eq!(dad.count, 1);
+        assert_eq!(
+            attempt(alice, "I'm still hungry").await?.outcome,
+            DadOutcome::CoolingDown(Cooldown::User)
+        );
+
+        db::utils::opt_outs::opt_out(&*dadder.db.lock().await, bob, None).await?;
+        assert_eq!(
+            attempt(bob, "I'm hungry").await?.outcome,
+            DadOutcome::OptedOut
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_over_transport() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![String::from("Hi {dad_text}, {sender}!").into()]);
+        config.rich_replies = Some(false);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        transport.set_display_name(room_id, alice, "Alice").await;
+
+        let own = IncomingMessage::text(room_id, "@dad:example.org", "$own", "I'm Dad");
+        assert_eq!(dadder.handle_message(&transport, &own).await?, None);
+        let mut notice = IncomingMessage::text(room_id, alice, "$notice", "I'm hungry");
+        notice.kind = MessageKind::Notice;
+        assert_eq!(dadder.handle_message(&transport, &notice).await?, None);
+        assert!(transport.sent().await.is_empty());
+
+        let message = IncomingMessage::text(room_id, alice, "$trigger", "I'm hungry");
+        let attempt = dadder.handle_message(&transport, &message).await?.unwrap();
+        assert_eq!(
+            attempt.reply().map(|reply| reply.text),
+            Some(String::from("Hi hungry, Alice!"))
+        );
+        assert_eq!(
+            transport.sent().await,
+            vec![SentEvent::Message {
+                room_id: room_id.to_string(),
+                event_id: String::from("$sent-1"),
+                kind: MessageKind::Text,
+                body: String::from("Hi hungry, Alice!"),
+                reply_to: None,
+                mention: None,
+            }]
+        );
+        let db = &*dadder.db.lock().await;
+        let events = db::utils::dad_events::get_dad_events_from_room(db, room_id).await?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].trigger_event_id, "$trigger");
+        assert_eq!(events[0].reply_event_id, "$sent-1");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_replies() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(true);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+
+        let message = IncomingMessage::text(room_id, "@alice:example.org", "$alice", "I'm hungry");
+        dadder.handle_message(&transport, &message).await?;
+        let mut threaded = IncomingMessage::text(room_id, "@bob:example.org", "$bob", "I'm tired");
+        threaded.thread_root = Some(String::from("$root"));
+        dadder.handle_message(&transport, &threaded).await?;
+        assert_eq!(
+            transport.sent().await,
+            vec![
+                SentEvent::Message {
+                    room_id: room_id.to_string(),
+                    event_id: String::from("$sent-1"),
+                    kind: MessageKind::Text,
+                    body: String::from("Hi hungry! I'm Dad!"),
+                    reply_to: Some(ReplyTo {
+                        event_id: String::from("$alice"),
+                        sender: String::from("@alice:example.org"),
+                        body: String::from("I'm hungry"),
+                        quote: true,
+                        thread_root: None,
+                    }),
+                    mention: None,
+                },
+                SentEvent::Message {
+                    room_id: room_id.to_string(),
+                    event_id: String::from("$sent-2"),
+                    kind: MessageKind::Text,
+                    body: String::from("Hi tired! I'm Dad!"),
+                    reply_to: Some(ReplyTo {
+                        event_id: String::from("$bob"),
+                        sender: String::from("@bob:example.org"),
+                        body: String::from("I'm tired"),
+                        quote: true,
+                        thread_root: Some(String::from("$root")),
+                    }),
+                    mention: None,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_edits() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(false);
+        config.update_on_edit = Some(false);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let edit = |event_id: &str, replaces: &str, body: &str| {
+            let mut edit = IncomingMessage::text(room_id, alice, event_id, body);
+            edit.replaces = Some(replaces.to_string());
+            edit
+        };
+
+        let message = IncomingMessage::text(room_id, alice, "$trigger", "I'm hungry");
+        dadder.handle_message(&transport, &message).await?;
+        let ignored = edit("$edit-1", "$trigger", "I'm thirsty");
+        assert_eq!(dadder.handle_message(&transport, &ignored).await?, None);
+        assert_eq!(transport.sent().await.len(), 1);
+
+        dadder.config.lock().await.update_on_edit = Some(true);
+        let same = edit("$edit-2", "$trigger", "Well I'm hungry");
+        dadder.handle_message(&transport, &same).await?;
+        let not_dadded = edit("$edit-3", "$other", "I'm thirsty");
+        dadder.handle_message(&transport, &not_dadded).await?;
+        assert_eq!(transport.sent().await.len(), 1);
+
+        let changed = edit("$edit-4", "$trigger", "I'm thirsty");
+        assert_eq!(dadder.handle_message(&transport, &changed).await?, None);
+        assert_eq!(
+            transport.sent().await.last(),
+            Some(&SentEvent::Edit {
+                room_id: room_id.to_string(),
+                event_id: String::from("$sent-2"),
+                replaces: String::from("$sent-1"),
+                kind: MessageKind::Text,
+                body: String::from("Hi thirsty! I'm Dad!"),
+                mention: None,
+            })
+        );
+        {
+            let db = &*dadder.db.lock().await;
+            let events = db::utils::dad_events::get_dad_events_from_room(db, room_id).await?;
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].dad_text, "thirsty");
+        }
+
+        // Edits go through the same checks as new messages
+        db::utils::opt_outs::opt_out(&*dadder.db.lock().await, alice, None).await?;
+        let opted_out = edit("$edit-5", "$trigger", "I'm sleepy");
+        dadder.handle_message(&transport, &opted_out).await?;
+        db::utils::opt_outs::opt_in(&*dadder.db.lock().await, alice, None).await?;
+        {
+            let db = &*dadder.db.lock().await;
+            db::utils::room_settings::set_room_setting(db, room_id, RoomSetting::Enabled(false))
+                .await?;
+        }
+        let disabled = edit("$edit-6", "$trigger", "I'm sleepy");
+        dadder.handle_message(&transport, &disabled).await?;
+        assert_eq!(transport.sent().await.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_edits_keep_template() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![
+            String::from("Hi {dad_text}!").into(),
+            String::from("Hello {dad_text}!").into(),
+        ]);
+        config.rich_replies = Some(false);
+        config.update_on_edit = Some(true);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+
+        let message = IncomingMessage::text(room_id, alice, "$trigger", "I'm hungry");
+        dadder.handle_message(&transport, &message).await?;
+        let greeting = match transport.sent().await.last() {
+            Some(SentEvent::Message { body, .. }) => body.replace("hungry!", ""),
+            sent => panic!("Expected a reply, got {:?}", sent),
+        };
+        for (idx, feeling) in ["thirsty", "sleepy", "bored"].iter().enumerate() {
+            let mut edit = IncomingMessage::text(
+                room_id,
+                alice,
+                &format!("$edit-{}", idx),
+                &format!("I'm {}", feeling),
+            );
+            edit.replaces = Some(String::from("$trigger"));
+            dadder.handle_message(&transport, &edit).await?;
+            match transport.sent().await.last() {
+                Some(SentEvent::Edit { body, .. }) => {
+                    assert_eq!(body, &format!("{}{}!", greeting, feeling))
+                }
+                sent => panic!("Expected an edit, got {:?}", sent),
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_emote_edits() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(false);
+        config.update_on_edit = Some(true);
+        config.mention_sender = Some(true);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        transport.set_display_name(room_id, alice, "Alice").await;
+
+        let mut emote = IncomingMessage::text(room_id, alice, "$trigger", "is hungry");
+        emote.kind = MessageKind::Emote;
+        dadder.handle_message(&transport, &emote).await?;
+        let mut edit = IncomingMessage::text(room_id, alice, "$edit", "is thirsty");
+        edit.kind = MessageKind::Emote;
+        edit.replaces = Some(String::from("$trigger"));
+        dadder.handle_message(&transport, &edit).await?;
+        assert_eq!(
+            transport.sent().await.last(),
+            Some(&SentEvent::Edit {
+                room_id: room_id.to_string(),
+                event_id: String::from("$sent-2"),
+                replaces: String::from("$sent-1"),
+                kind: MessageKind::Emote,
+                body: String::from("says: Hi thirsty! I'm Dad!"),
+                mention: Some(Mention {
+                    user_id: alice.to_string(),
+                    display_name: String::from("Alice"),
+                }),
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_first_person_emote() -> Result<(), Error> {
+        assert_eq!(get_first_person_emote("is so tired"), "I am so tired");
+        assert_eq!(get_first_person_emote(" Is hungry"), "I am hungry");
+        assert_eq!(get_first_person_emote("says I'm hungry"), "says I'm hungry");
+        assert_eq!(get_first_person_emote("isn't hungry"), "isn't hungry");
+        assert_eq!(get_first_person_emote("is"), "is");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_emotes_and_notices() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(false);
+        config.reply_to_notices = Some(true);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+
+        let mut emote =
+            IncomingMessage::text(room_id, "@alice:example.org", "$emote", "is so tired");
+        emote.kind = MessageKind::Emote;
+        dadder.handle_message(&transport, &emote).await?;
+        let mut notice = IncomingMessage::text(room_id, "@bot:example.org", "$notice", "I'm a bot");
+        notice.kind = MessageKind::Notice;
+        dadder.handle_message(&transport, &notice).await?;
+        assert_eq!(
+            transport.sent().await,
+            vec![
+                SentEvent::Message {
+                    room_id: room_id.to_string(),
+                    event_id: String::from("$sent-1"),
+                    kind: MessageKind::Emote,
+                    body: String::from("says: Hi so tired! I'm Dad!"),
+                    reply_to: None,
+                    mention: None,
+                },
+                SentEvent::Message {
+                    room_id: room_id.to_string(),
+                    event_id: String::from("$sent-2"),
+                    kind: MessageKind::Text,
+                    body: String::from("Hi a bot! I'm Dad!"),
+                    reply_to: None,
+                    mention: None,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_mentions_sender() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(false);
+        config.mention_sender = Some(true);
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        transport.set_display_name(room_id, alice, "*Alice*").await;
+
+        let message = IncomingMessage::text(room_id, alice, "$trigger", "I'm hungry");
+        dadder.handle_message(&transport, &message).await?;
+        assert_eq!(
+            transport.sent().await,
+            vec![SentEvent::Message {
+                room_id: room_id.to_string(),
+                event_id: String::from("$sent-1"),
+                kind: MessageKind::Text,
+                body: String::from("Hi hungry! I'm Dad!"),
+                reply_to: None,
+                mention: Some(Mention {
+                    user_id: alice.to_string(),
+                    display_name: String::from("\\*Alice\\*"),
+                }),
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_reacts() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.dadded_chance = None;
+        config.love_me_chance = None;
+        config.templates = Some(vec![DEFAULT_TEMPLATE.into()]);
+        config.rich_replies = Some(false);
+        config.dad_mode = Some("both".into());
+        config.reaction = Some("👨".into());
+        let dadder = create_dadder(config).await?;
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        {
+            let db = &*dadder.db.lock().await;
+            db::utils::room_settings::set_room_setting(
+                db,
+                room_id,
+                RoomSetting::DadMode(Some(String::from("react"))),
+            )
+            .await?;
+        }
+
+        let message = IncomingMessage::text(room_id, "@alice:example.org", "$alice", "I'm hungry");
+        dadder.handle_message(&transport, &message).await?;
+        let other_room_id = "!other:example.org";
+        let message = IncomingMessage::text(other_room_id, "@bob:example.org", "$bob", "I'm tired");
+        dadder.handle_message(&transport, &message).await?;
+        assert_eq!(
+            transport.sent().await,
+            vec![
+                SentEvent::Reaction {
+                    room_id: room_id.to_string(),
+                    event_id: String::from("$sent-1"),
+                    relates_to: String::from("$alice"),
+                    key: String::from("👨"),
+                },
+                SentEvent::Message {
+                    room_id: other_room_id.to_string(),
+                    event_id: String::from("$sent-2"),
+                    kind: MessageKind::Text,
+                    body: String::from("Hi tired! I'm Dad!"),
+                    reply_to: None,
+                    mention: None,
+                },
+                SentEvent::Reaction {
+                    room_id: other_room_id.to_string(),
+                    event_id: String::from("$sent-3"),
+                    relates_to: String::from("$bob"),
+                    key: String::from("👨"),
+                },
+            ]
+        );
+
+        let mgr = &mut *dadder.dad_handler.lock().await;
+        let db = &*dadder.db.lock().await;
+        let room_dad = mgr.get_current_dad(db, room_id).await?;
+        let other_room_dad = mgr.get_current_dad(db, other_room_id).await?;
+        assert_eq!((room_dad.count, room_dad.reaction_count), (0, 1));
+        // Both counts the reply and the reaction, but the victim only once
+        assert_eq!(
+            (other_room_dad.count, other_room_dad.reaction_count),
+            (1, 1)
+        );
+        assert_eq!(
+            db::utils::victims::get_leaderboard(db, other_room_id, None).await?,
+            vec![(String::from("@bob:example.org"), 1)]
+        );
+        // Reactions can't be edited, so they aren't logged as dad events
+        assert!(db::utils::dad_events::get_dad_events_from_room(db, room_id)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+}
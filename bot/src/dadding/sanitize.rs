@@ -0,0 +1,98 @@
+// Markdown and HTML characters that could turn echoed text into formatting, links or tags
+const ESCAPED: &str = "\\`*_[]()<>&~|#!";
+// Keeps `@room` and MXIDs from pinging anyone without changing how they look
+const WORD_JOINER: char = '\u{2060}';
+const ELLIPSIS: char = '…';
+
+/// Position of the character that makes the text a list item or heading when it starts a line,
+/// like the `-` in `- item` or the `.` in `1. item`
+fn get_block_marker(chars: &[char]) -> Option<usize> {
+    let start = chars.iter().position(|c| *c != ' ')?;
+    match chars[start] {
+        '-' | '+' | '=' => Some(start),
+        '0'..='9' => {
+            let end = start + chars[start..].iter().position(|c| !c.is_ascii_digit())?;
+            if chars[end] == '.' {
+                Some(end)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Makes user supplied text safe to put into a markdown reply, capped at `max_length` characters
+pub fn sanitize(text: &str, max_length: usize) -> String {
+    let chars = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<Vec<_>>();
+    // Templates can put the text at the start of a line, where these start a block
+    let block_marker = get_block_marker(&chars);
+    let mut sanitized = String::with_capacity(text.len());
+    let mut length = 0;
+    for (idx, c) in chars.into_iter().enumerate() {
+        if length == max_length {
+            sanitized.push(ELLIPSIS);
+            break;
+        }
+        if ESCAPED.contains(c) || block_marker == Some(idx) {
+            sanitized.push('\\');
+        }
+        sanitized.push(c);
+        if c == '@' {
+            sanitized.push(WORD_JOINER);
+        }
+        length += 1;
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    #[tokio::test]
+    async fn test_escapes_formatting() -> Result<(), Error> {
+        assert_eq!(
+            sanitize("**bold** [click](http://evil)", 100),
+            "\\*\\*bold\\*\\* \\[click\\]\\(http://evil\\)"
+        );
+        assert_eq!(
+            sanitize("<b>hi</b> & `code`", 100),
+            "\\<b\\>hi\\</b\\> \\& \\`code\\`"
+        );
+        assert_eq!(sanitize("so tired", 100), "so tired");
+        assert_eq!(sanitize("- not a list", 100), "\\- not a list");
+        assert_eq!(sanitize("  + not a list", 100), "  \\+ not a list");
+        assert_eq!(sanitize("=== not a heading", 100), "\\=== not a heading");
+        assert_eq!(sanitize("12. not a list", 100), "12\\. not a list");
+        assert_eq!(
+            sanitize("12 is fine - really. 1. ok", 100),
+            "12 is fine - really. 1. ok"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_neutralizes_mentions() -> Result<(), Error> {
+        assert_eq!(sanitize("@room", 100), "@\u{2060}room");
+        assert_eq!(
+            sanitize("@alice:example.org", 100),
+            "@\u{2060}alice:example.org"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_caps_length() -> Result<(), Error> {
+        assert_eq!(sanitize("hungry", 6), "hungry");
+        assert_eq!(sanitize("so very hungry", 7), "so very…");
+        assert_eq!(sanitize("ümlaut", 2), "üm…");
+        assert_eq!(sanitize("line\nbreak", 100), "line break");
+        assert_eq!(sanitize("", 0), "");
+        Ok(())
+    }
+}
@@ -9,6 +9,18 @@ pub enum Error {
     Send(#[from] tokio::sync::mpsc::error::SendError<AnyMessageEventContent>),
     #[error(transparent)]
     DbError(#[from] DbError),
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error(transparent)]
+    Matrix(#[from] matrix_sdk::Error),
+    #[error("Transport error: {0}")]
+    Transport(String),
+    #[error("There is no locale pack for [{0}]")]
+    UnknownLocale(String),
+    #[error("Couldn't load config: {0}")]
+    ConfigLoad(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
 }
 
 impl From<db::sea_orm::DbErr> for Error {
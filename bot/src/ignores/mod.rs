@@ -0,0 +1,149 @@
+use crate::config::Config;
+use crate::errors::Error;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use tracing::*;
+
+/// Turns an ignored user entry into a regex, `/.../` entries already are one and
+/// everything else is a glob where `*` is any text and `?` any one character
+fn user_pattern(entry: &str) -> Result<Regex, Error> {
+    let pattern = match entry.strip_prefix('/').and_then(|e| e.strip_suffix('/')) {
+        Some(regex) => regex.to_string(),
+        None => {
+            let glob = entry
+                .chars()
+                .map(|c| match c {
+                    '*' => String::from(".*"),
+                    '?' => String::from("."),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect::<String>();
+            format!("^{}$", glob)
+        }
+    };
+    let regex = RegexBuilder::new(&pattern).case_insensitive(true).build()?;
+    Ok(regex)
+}
+
+fn server_name(mxid: &str) -> Option<&str> {
+    mxid.split_once(':').map(|(_, server)| server)
+}
+
+/// Senders and rooms the bot never answers, from the config and the bot account's
+/// `m.ignored_user_list`
+#[derive(Debug, Clone)]
+pub struct Ignores {
+    users: Vec<Regex>,
+    servers: Vec<String>,
+    rooms: Vec<String>,
+    // Kept when the config is reloaded, the homeserver tells us when it changes
+    account_users: HashSet<String>,
+}
+
+impl Ignores {
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let users = config
+            .ignored_users
+            .iter()
+            .flatten()
+            .map(|entry| user_pattern(entry))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            users,
+            servers: config
+                .ignored_servers
+                .iter()
+                .flatten()
+                .map(|server| server.to_lowercase())
+                .collect(),
+            rooms: config
+                .ignored_rooms
+                .iter()
+                .flatten()
+                .map(|room| room.to_string())
+                .collect(),
+            account_users: HashSet::new(),
+        })
+    }
+
+    /// Takes the config lists of `new`, keeping the account's ignored users
+    pub fn replace_config_lists(&mut self, new: Ignores) {
+        self.users = new.users;
+        self.servers = new.servers;
+        self.rooms = new.rooms;
+    }
+
+    pub fn set_account_users<I>(&mut self, users: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.account_users = users.into_iter().collect();
+        info!("Account ignores {} users", self.account_users.len());
+    }
+
+    pub fn is_ignored(&self, room_id: &str, sender: &str) -> bool {
+        if self.rooms.iter().any(|room| room == room_id) {
+            debug!("{} is an ignored room", room_id);
+            return true;
+        }
+        if self.account_users.contains(sender) {
+            debug!("{} is on the account's ignore list", sender);
+            return true;
+        }
+        let server = server_name(sender).map(|server| server.to_lowercase());
+        if let Some(server) = server.filter(|server| self.servers.contains(server)) {
+            debug!("{} is on the ignored server {}", sender, server);
+            return true;
+        }
+        if self.users.iter().any(|user| user.is_match(sender)) {
+            debug!("{} is an ignored user", sender);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_utils::load_example_config;
+
+    #[tokio::test]
+    async fn test_user_pattern() -> Result<(), Error> {
+        let glob = user_pattern("@*bot:example.org")?;
+        assert!(glob.is_match("@cibot:example.org"));
+        assert!(glob.is_match("@CIBot:example.org"));
+        assert!(!glob.is_match("@cibot:example.org.evil"));
+        assert!(!glob.is_match("@robert:example.org"));
+        let single = user_pattern("@bot?:example.org")?;
+        assert!(single.is_match("@bot1:example.org"));
+        assert!(!single.is_match("@bot12:example.org"));
+        let regex = user_pattern(r"/^@(irc|slack)_.+:/")?;
+        assert!(regex.is_match("@irc_alice:example.org"));
+        assert!(!regex.is_match("@alice:example.org"));
+        assert!(user_pattern("/(/").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_ignored() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.ignored_users = Some(vec!["@*bot:example.org".into()]);
+        config.ignored_servers = Some(vec!["Bridge.example.org".into()]);
+        config.ignored_rooms = Some(vec!["!noisy:example.org".into()]);
+        let mut ignores = Ignores::from_config(&config)?;
+        let room_id = "!room:example.org";
+
+        assert!(ignores.is_ignored(room_id, "@cibot:example.org"));
+        assert!(ignores.is_ignored(room_id, "@alice:bridge.example.org"));
+        assert!(ignores.is_ignored("!noisy:example.org", "@alice:example.org"));
+        assert!(!ignores.is_ignored(room_id, "@alice:example.org"));
+
+        ignores.set_account_users(vec![String::from("@alice:example.org")]);
+        assert!(ignores.is_ignored(room_id, "@alice:example.org"));
+        ignores.replace_config_lists(Ignores::from_config(&load_example_config())?);
+        assert!(ignores.is_ignored(room_id, "@alice:example.org"));
+        assert!(!ignores.is_ignored(room_id, "@cibot:example.org"));
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 #[cfg(test)]
 mod utils {
+    use crate::config::Config;
     use crate::errors::Error;
     use db::migration::*;
     use db::sea_orm::*;
+    use mrsbfh::config::Loader;
     use rand::rngs::mock::StepRng;
     use rand::{RngCore, SeedableRng};
 
@@ -12,6 +14,14 @@ mod utils {
         Ok(db)
     }
 
+    pub fn load_example_config() -> Config<'static> {
+        Config::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../config.yaml.example"
+        ))
+        .unwrap()
+    }
+
     const N: usize = 8;
     pub struct SeedableStepSeed(pub [u8; N]);
 
@@ -70,4 +80,5 @@ mod utils {
 }
 
 pub use self::utils::create_inmemory_db;
+pub use self::utils::load_example_config;
 pub use self::utils::SeedableStepRng;
@@ -0,0 +1,282 @@
+use crate::config::Config;
+use crate::transport::{Membership, MembershipChange, Transport};
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::*;
+
+const MAX_JOIN_DELAY: Duration = Duration::from_secs(3600);
+
+fn lowercase(entries: &Option<Vec<Cow<'_, str>>>) -> Vec<String> {
+    entries
+        .iter()
+        .flatten()
+        .map(|entry| entry.to_lowercase())
+        .collect()
+}
+
+/// Who may invite the bot and to which rooms
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvitePolicy {
+    // Owners can always invite, everyone can when both lists are empty
+    owners: Vec<String>,
+    allowed_users: Vec<String>,
+    allowed_servers: Vec<String>,
+    max_members: Option<u64>,
+    accept_direct: bool,
+}
+
+impl InvitePolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            owners: lowercase(&config.owners),
+            allowed_users: lowercase(&config.invite_allowed_users),
+            allowed_servers: lowercase(&config.invite_allowed_servers),
+            max_members: config.invite_max_members,
+            accept_direct: config.get_accept_direct_invites(),
+        }
+    }
+
+    fn is_allowed_inviter(&self, inviter: &str) -> bool {
+        let inviter = inviter.to_lowercase();
+        if self.owners.contains(&inviter) {
+            return true;
+        }
+        if self.allowed_users.is_empty() && self.allowed_servers.is_empty() {
+            return true;
+        }
+        let server = inviter.split_once(':').map(|(_, server)| server);
+        self.allowed_users.contains(&inviter)
+            || server.map_or(false, |server| {
+                self.allowed_servers.iter().any(|allowed| allowed == server)
+            })
+    }
+
+    /// Why an invite should be rejected, `None` if it can be accepted.
+    /// `member_count` is `None` when the room's size isn't known yet
+    pub fn check(&self, change: &MembershipChange, member_count: Option<u64>) -> Option<String> {
+        if !self.is_allowed_inviter(&change.sender) {
+            return Some(format!("{} isn't allowed to invite me", change.sender));
+        }
+        if change.is_direct && !self.accept_direct {
+            return Some(String::from("direct messages are turned off"));
+        }
+        match (member_count, self.max_members) {
+            (Some(count), Some(max)) if count > max => Some(format!(
+                "it has {} members, the most I join is {}",
+                count, max
+            )),
+            _ => None,
+        }
+    }
+}
+
+async fn join_with_retries<Tr>(transport: &Tr, room_id: &str) -> bool
+where
+    Tr: Transport + ?Sized,
+{
+    let mut delay = Duration::from_secs(2);
+    while let Err(e) = transport.join_room(room_id).await {
+        if delay > MAX_JOIN_DELAY {
+            error!("Giving up on joining {}: {}", room_id, e);
+            return false;
+        }
+        warn!(
+            "Couldn't join {} ({}), retrying in {}s",
+            room_id,
+            e,
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    true
+}
+
+async fn join_and_recheck<Tr>(transport: &Tr, change: &MembershipChange, policy: &InvitePolicy)
+where
+    Tr: Transport + ?Sized,
+{
+    if !join_with_retries(transport, &change.room_id).await {
+        return;
+    }
+    info!("Joined {}", change.room_id);
+    // Invites rarely say how big the room is, so check again now that it's known
+    let member_count = transport.member_count(&change.room_id).await;
+    if let Some(reason) = policy.check(change, member_count) {
+        info!("Leaving {} right after joining: {}", change.room_id, reason);
+        if let Err(e) = transport.leave_room(&change.room_id).await {
+            error!("Couldn't leave {}: {}", change.room_id, e);
+        }
+    }
+}
+
+/// Accepts or rejects invites for the bot according to `policy`. Joins are retried with a
+/// growing delay since the homeserver may not know about the room yet, which happens in a task
+/// of its own so the sync loop doesn't wait on it. The handle is for waiting on the join
+pub async fn on_membership_change<Tr>(
+    transport: Arc<Tr>,
+    change: &MembershipChange,
+    policy: &InvitePolicy,
+) -> Option<JoinHandle<()>>
+where
+    Tr: Transport + ?Sized + 'static,
+{
+    if change.membership != Membership::Invite || change.user_id != transport.own_user_id() {
+        return None;
+    }
+    info!("{} invited me to {}", change.sender, change.room_id);
+    let member_count = transport.member_count(&change.room_id).await;
+    if let Some(reason) = policy.check(change, member_count) {
+        info!("Rejecting the invite to {}: {}", change.room_id, reason);
+        if let Err(e) = transport.leave_room(&change.room_id).await {
+            error!("Couldn't reject the invite to {}: {}", change.room_id, e);
+        }
+        return None;
+    }
+    info!("Accepting the invite to {}", change.room_id);
+    let change = change.clone();
+    let policy = policy.clone();
+    Some(tokio::spawn(async move {
+        join_and_recheck(&*transport, &change, &policy).await;
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+    use crate::integration_utils::load_example_config;
+    use crate::transport::InMemoryTransport;
+    use tokio::time::timeout;
+
+    fn invite(user_id: &str, sender: &str) -> MembershipChange {
+        MembershipChange {
+            room_id: String::from("!room:example.org"),
+            user_id: user_id.to_string(),
+            sender: sender.to_string(),
+            membership: Membership::Invite,
+            is_direct: false,
+        }
+    }
+
+    fn open_policy() -> InvitePolicy {
+        let mut config = load_example_config();
+        config.invite_allowed_users = None;
+        config.invite_allowed_servers = None;
+        config.invite_max_members = None;
+        config.accept_direct_invites = None;
+        InvitePolicy::from_config(&config)
+    }
+
+    // Handles the change and waits for the join it started, if any
+    async fn handle(
+        transport: &Arc<InMemoryTransport>,
+        change: &MembershipChange,
+        policy: &InvitePolicy,
+    ) {
+        if let Some(join) = on_membership_change(Arc::clone(transport), change, policy).await {
+            join.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_joins_when_invited() -> Result<(), Error> {
+        let transport = Arc::new(InMemoryTransport::new("@dad:example.org"));
+        let policy = open_policy();
+        let alice = "@alice:example.org";
+        handle(&transport, &invite("@bob:example.org", alice), &policy).await;
+        assert!(
+            !transport
+                .is_member("!room:example.org", "@dad:example.org")
+                .await
+        );
+        handle(&transport, &invite("@dad:example.org", alice), &policy).await;
+        assert!(
+            transport
+                .is_member("!room:example.org", "@dad:example.org")
+                .await
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_join_retries_dont_block() -> Result<(), Error> {
+        let transport = Arc::new(InMemoryTransport::new("@dad:example.org"));
+        transport.set_unjoinable("!room:example.org").await;
+        let change = invite("@dad:example.org", "@alice:example.org");
+
+        // The first retry alone waits longer than this
+        let join = timeout(
+            Duration::from_secs(1),
+            on_membership_change(Arc::clone(&transport), &change, &open_policy()),
+        )
+        .await
+        .expect("waited on the join retries")
+        .expect("accepted the invite");
+        join.abort();
+        assert!(
+            !transport
+                .is_member("!room:example.org", "@dad:example.org")
+                .await
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invite_policy() -> Result<(), Error> {
+        let mut config = load_example_config();
+        config.owners = Some(vec!["@owner:elsewhere.org".into()]);
+        config.invite_allowed_users = Some(vec!["@Alice:example.org".into()]);
+        config.invite_allowed_servers = Some(vec!["friends.org".into()]);
+        config.invite_max_members = Some(10);
+        config.accept_direct_invites = Some(false);
+        let policy = InvitePolicy::from_config(&config);
+        let dad = "@dad:example.org";
+
+        assert_eq!(policy.check(&invite(dad, "@alice:example.org"), None), None);
+        assert_eq!(
+            policy.check(&invite(dad, "@bob:friends.org"), Some(10)),
+            None
+        );
+        assert_eq!(
+            policy.check(&invite(dad, "@owner:elsewhere.org"), None),
+            None
+        );
+        assert!(policy
+            .check(&invite(dad, "@mallory:example.org"), None)
+            .is_some());
+        assert!(policy
+            .check(&invite(dad, "@bob:friends.org.evil"), None)
+            .is_some());
+        assert!(policy
+            .check(&invite(dad, "@alice:example.org"), Some(11))
+            .is_some());
+        let mut direct = invite(dad, "@alice:example.org");
+        direct.is_direct = true;
+        assert!(policy.check(&direct, None).is_some());
+        assert_eq!(open_policy().check(&direct, Some(1000)), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_and_leaves_by_policy() -> Result<(), Error> {
+        let transport = Arc::new(InMemoryTransport::new("@dad:example.org"));
+        let mut config = load_example_config();
+        config.invite_allowed_users = Some(vec!["@alice:example.org".into()]);
+        config.invite_max_members = Some(2);
+        let policy = InvitePolicy::from_config(&config);
+        let room_id = "!room:example.org";
+        let dad = "@dad:example.org";
+
+        handle(&transport, &invite(dad, "@mallory:example.org"), &policy).await;
+        assert!(!transport.is_member(room_id, dad).await);
+
+        transport.add_member(room_id, "@alice:example.org").await;
+        transport.add_member(room_id, "@bob:example.org").await;
+        handle(&transport, &invite(dad, "@alice:example.org"), &policy).await;
+        assert!(!transport.is_member(room_id, dad).await);
+        Ok(())
+    }
+}
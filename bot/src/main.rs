@@ -2,7 +2,8 @@ extern crate clap;
 extern crate lazy_static;
 
 use crate::config::Config;
-use clap::Parser;
+use crate::errors::Error as DadError;
+use clap::{Parser, Subcommand};
 use db::migration::*;
 use db::sea_orm::*;
 use mrsbfh::config::Loader;
@@ -11,10 +12,16 @@ use tracing::*;
 
 mod commands;
 mod config;
+mod dadding;
 mod errors;
+mod ignores;
 #[cfg(test)]
 mod integration_utils;
+mod invites;
 mod matrix;
+mod repl;
+mod transport;
+mod triggers;
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -31,6 +38,40 @@ struct Args {
         value_name = "FILE"
     )]
     config: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the config for mistakes without logging in
+    CheckConfig,
+    /// Chat with dad on stdin using a local database instead of Matrix
+    Repl {
+        #[clap(long, default_value = "sqlite://./repl.db?mode=rwc")]
+        db: String,
+        #[clap(long, default_value = "!repl:localhost")]
+        room: String,
+        #[clap(long, default_value = "@you:localhost")]
+        sender: String,
+        /// Seed the RNG to get the same rolls every run
+        #[clap(long)]
+        seed: Option<u64>,
+    },
+}
+
+fn check_config(config_path: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+    let problems = config::find_problems(config);
+    if problems.is_empty() {
+        println!("{} looks good!", config_path);
+        Ok(())
+    } else {
+        println!("{} has {} problem(s):", config_path, problems.len());
+        for problem in &problems {
+            println!("- {}", problem);
+        }
+        Err(Box::new(DadError::InvalidConfig(problems.join("; "))))
+    }
 }
 
 #[tokio::main]
@@ -45,7 +86,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
     debug!("Creating arguments...");
     let args = Args::parse();
     info!("Loading configs...");
-    let config = Config::load(args.config)?;
+    let config = Config::load(args.config.clone())?;
+    match args.command {
+        Some(Command::CheckConfig) => return check_config(&args.config, &config),
+        Some(Command::Repl {
+            db,
+            room,
+            sender,
+            seed,
+        }) => return repl::run(config, &db, room, sender, seed).await,
+        None => {}
+    }
+    // Only check-config refuses a config, the bot starts like it always has and says what's off
+    for problem in config::find_problems(&config) {
+        warn!("{}", problem);
+    }
     info!("Setting up Client...");
     let client = &mut matrix::setup(config.clone()).await?;
     info!("Createing DB connection...");
@@ -59,6 +114,6 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Migrator::up(&db, None).await?;
 
     info!("Starting Sync...");
-    matrix::start_sync(client, config, db).await?;
+    matrix::start_sync(client, config, args.config, db).await?;
     Ok(())
 }
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Duration, Local};
+use tracing::*;
+
+/// Skips events sent before the bot started, the first sync replays recent timelines and
+/// those messages are usually long answered
+#[derive(Debug, Clone)]
+pub struct BacklogFilter {
+    cutoff: DateTime<Local>,
+    skipped: u64,
+    unreported: u64,
+}
+
+impl BacklogFilter {
+    /// Events sent up to `grace` before `started` are still handled
+    pub fn new(started: DateTime<Local>, grace: Duration) -> Self {
+        Self {
+            cutoff: started - grace,
+            skipped: 0,
+            unreported: 0,
+        }
+    }
+
+    /// Whether an event sent at `sent_at` is backlog, counting it if it is
+    pub fn is_backlog(&mut self, sent_at: DateTime<Local>) -> bool {
+        if sent_at < self.cutoff {
+            self.skipped += 1;
+            self.unreported += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Logs how many events were skipped since the last report, if any
+    pub fn report(&mut self) {
+        if self.unreported > 0 {
+            info!(
+                "Skipped {} events from before {}, {} in total",
+                self.unreported, self.cutoff, self.skipped
+            );
+            self.unreported = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    #[tokio::test]
+    async fn test_backlog_filter() -> Result<(), Error> {
+        let started = Local::now();
+        let mut filter = BacklogFilter::new(started, Duration::seconds(30));
+
+        assert!(filter.is_backlog(started - Duration::hours(3)));
+        assert!(filter.is_backlog(started - Duration::seconds(31)));
+        assert!(!filter.is_backlog(started - Duration::seconds(10)));
+        assert!(!filter.is_backlog(started + Duration::minutes(5)));
+        assert_eq!(filter.skipped, 2);
+
+        filter.report();
+        assert_eq!(filter.unreported, 0);
+        assert_eq!(filter.skipped, 2);
+        Ok(())
+    }
+}
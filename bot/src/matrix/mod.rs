@@ -1,9 +1,25 @@
-use crate::commands::utils::{DaddedManager, RngManager};
+use crate::commands::utils::{get_command_context, DaddedManager, RngManager};
 use crate::config::Config;
-use chrono::Local;
+use crate::ignores::Ignores;
+use crate::invites::{on_membership_change, InvitePolicy};
+use crate::transport::{membership_from_event, message_from_event, MatrixTransport};
+use crate::triggers::Triggers;
+use chrono::{Local, TimeZone};
 use db::sea_orm::DbConn;
-use db::utils::{dadded, epochs};
-use matrix_sdk::{ruma::MxcUri, Client, ClientConfig, Session as SDKSession, SyncSettings};
+use db::utils::epochs;
+use matrix_sdk::{
+    event_handler::RawEvent,
+    room::Room,
+    ruma::{
+        events::{
+            ignored_user_list::IgnoredUserListEventContent,
+            room::{member::MemberEventContent, message::MessageEventContent},
+            GlobalAccountDataEvent, StrippedStateEvent, SyncMessageEvent,
+        },
+        MxcUri,
+    },
+    Client, ClientConfig, LoopCtrl, Session as SDKSession, SyncSettings,
+};
 use mrsbfh::{url::Url, utils::Session};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
@@ -11,8 +27,13 @@ use std::{convert::TryFrom, error::Error, fs, path::Path, sync::Arc};
 use tokio::sync::Mutex;
 use tracing::*;
 
+mod backlog;
+mod reload;
 mod sync;
 
+use self::backlog::BacklogFilter;
+use self::reload::ConfigReloader;
+
 pub async fn setup(config: Config<'_>) -> Result<Client, Box<dyn Error>> {
     info!("Beginning Matrix Setup");
     let store_path_string = config.store_path.to_string();
@@ -22,18 +43,17 @@ pub async fn setup(config: Config<'_>) -> Result<Client, Box<dyn Error>> {
     }
     let client_config = ClientConfig::new().store_path(fs::canonicalize(&store_path)?);
 
-    let homeserver_url =
-        Url::parse(&config.homeserver_url).expect("Couldn't parse the homeserver URL");
+    let homeserver_url = Url::parse(&config.homeserver_url)?;
 
-    let client = Client::new_with_config(homeserver_url, client_config).unwrap();
+    let client = Client::new_with_config(homeserver_url, client_config)?;
 
-    if let Some(session) = Session::load(config.session_path.parse().unwrap()) {
+    if let Some(session) = Session::load(config.session_path.parse()?) {
         info!("Starting relogin");
 
         let session = SDKSession {
             access_token: session.access_token,
             device_id: session.device_id.into(),
-            user_id: matrix_sdk::ruma::UserId::try_from(session.user_id.as_str()).unwrap(),
+            user_id: matrix_sdk::ruma::UserId::try_from(session.user_id.as_str())?,
         };
 
         if let Err(e) = client.restore_login(session).await {
@@ -54,7 +74,7 @@ pub async fn setup(config: Config<'_>) -> Result<Client, Box<dyn Error>> {
                     access_token: login_response.access_token,
                     device_id: login_response.device_id.into(),
                 };
-                session.save(config.session_path.parse().unwrap())?;
+                session.save(config.session_path.parse()?)?;
             }
             Err(e) => error!("Error while login: {}", e),
         }
@@ -93,27 +113,46 @@ pub async fn setup(config: Config<'_>) -> Result<Client, Box<dyn Error>> {
 pub async fn start_sync(
     client: &mut Client,
     config: Config<'static>,
+    config_path: String,
     db: DbConn,
 ) -> Result<(), Box<dyn Error>> {
-    client.register_event_handler(mrsbfh::sync::autojoin).await;
     let config = Arc::new(Mutex::new(config));
     let cloned_config = Arc::clone(&config);
+    let invite_config = Arc::clone(&config);
+    client
+        .register_event_handler(
+            move |ev: StrippedStateEvent<MemberEventContent>, room: Room, client: Client| {
+                let handler_config = Arc::clone(&invite_config);
+                async move {
+                    if let Some(change) = membership_from_event(&ev, &room) {
+                        let policy = InvitePolicy::from_config(&*handler_config.lock().await);
+                        let transport =
+                            MatrixTransport::new(client, room.own_user_id().to_string());
+                        on_membership_change(Arc::new(transport), &change, &policy).await;
+                    }
+                }
+            },
+        )
+        .await;
     let db = Arc::new(Mutex::new(db));
     let cloned_db = Arc::clone(&db);
 
     let now = Local::now();
     let config_options = cloned_config.lock().await.clone();
 
+    let backlog = Arc::new(Mutex::new(BacklogFilter::new(
+        now,
+        config_options.get_backlog_grace(),
+    )));
+    let cloned_backlog = Arc::clone(&backlog);
+
     let epoch_length = config_options.get_epoch_length();
     info!("Initalizing Dadded Epoch Manager...");
     let epoch = epochs::get_or_create_epoch(&*db.lock().await, &now, epoch_length).await?;
     let next_epoch =
         epochs::get_next_epoch_bound(&*db.lock().await, epoch.id, epoch_length).await?;
-    let dadded = dadded::get_or_create_dad_from_epoch(&*db.lock().await, epoch.id).await?;
 
-    let dad_manager = Arc::new(Mutex::new(DaddedManager::new(
-        epoch.id, next_epoch, dadded.id,
-    )));
+    let dad_manager = Arc::new(Mutex::new(DaddedManager::new(epoch.id, next_epoch)));
     info!("Intializing Dadded RNG Manager...");
 
     let dadded_chance = config_options.dadded_chance;
@@ -124,26 +163,97 @@ pub async fn start_sync(
         love_me_chance,
         rng,
     )));
+    info!("Compiling Dadded Triggers...");
+    let triggers = Arc::new(Mutex::new(Triggers::from_config(&config_options)?));
+    let ignores = Arc::new(Mutex::new(Ignores::from_config(&config_options)?));
+    let account_ignores = Arc::clone(&ignores);
     client
-        .register_event_handler(move |ev, room, client| {
-            let handler_config = Arc::clone(&cloned_config);
-            let handler_db = Arc::clone(&cloned_db);
-            let handler_dad_manager = Arc::clone(&dad_manager);
-            let handler_rng_manager = Arc::clone(&rng_manager);
-            sync::on_room_message(
-                ev,
-                room,
-                client,
-                handler_config,
-                handler_db,
-                handler_dad_manager,
-                handler_rng_manager,
-            )
-        })
+        .register_event_handler(
+            move |ev: GlobalAccountDataEvent<IgnoredUserListEventContent>| {
+                let handler_ignores = Arc::clone(&account_ignores);
+                async move {
+                    let users = ev.content.ignored_users.iter().map(|u| u.to_string());
+                    handler_ignores.lock().await.set_account_users(users);
+                }
+            },
+        )
+        .await;
+
+    let reloader = ConfigReloader::new(
+        config_path,
+        Arc::clone(&config),
+        Arc::clone(&triggers),
+        Arc::clone(&ignores),
+        Arc::clone(&db),
+        Arc::clone(&dad_manager),
+        Arc::clone(&rng_manager),
+    );
+    tokio::spawn(reloader.watch_sighup());
+    client
+        .register_event_handler(
+            move |ev: SyncMessageEvent<MessageEventContent>,
+                  room: Room,
+                  client: Client,
+                  raw: RawEvent| {
+                let handler_config = Arc::clone(&cloned_config);
+                let handler_db = Arc::clone(&cloned_db);
+                let handler_dad_manager = Arc::clone(&dad_manager);
+                let handler_rng_manager = Arc::clone(&rng_manager);
+                let handler_triggers = Arc::clone(&triggers);
+                let handler_backlog = Arc::clone(&cloned_backlog);
+                let handler_ignores = Arc::clone(&ignores);
+                async move {
+                    // Replayed messages would be dadded late and tick epochs for old traffic
+                    let sent_at = Local.timestamp_millis(i64::from(ev.origin_server_ts.get()));
+                    if handler_backlog.lock().await.is_backlog(sent_at) {
+                        debug!("Skipping {} from before startup", ev.event_id);
+                        return;
+                    }
+                    let room_id = room.room_id().as_str();
+                    if handler_ignores
+                        .lock()
+                        .await
+                        .is_ignored(room_id, ev.sender.as_str())
+                    {
+                        return;
+                    }
+                    let message = match message_from_event(&ev, &room, &raw) {
+                        Some(message) => message,
+                        None => return,
+                    };
+                    let transport =
+                        MatrixTransport::new(client.clone(), room.own_user_id().to_string());
+                    let context =
+                        get_command_context(&transport, &message, Arc::clone(&handler_config))
+                            .await;
+                    sync::on_room_message(
+                        ev,
+                        room,
+                        client,
+                        handler_config,
+                        handler_db,
+                        handler_dad_manager,
+                        handler_rng_manager,
+                        handler_triggers,
+                        Arc::new(Mutex::new(message)),
+                        Arc::new(Mutex::new(context)),
+                    )
+                    .await
+                }
+            },
+        )
         .await;
 
     info!("Starting full Sync...");
-    client.sync(SyncSettings::default()).await;
+    client
+        .sync_with_callback(SyncSettings::default(), |_| {
+            let backlog = Arc::clone(&backlog);
+            async move {
+                backlog.lock().await.report();
+                LoopCtrl::Continue
+            }
+        })
+        .await;
 
     Ok(())
 }
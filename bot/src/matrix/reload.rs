@@ -0,0 +1,203 @@
+use crate::commands::utils::{DaddedManager, RngManager};
+use crate::config::{find_problems, Config};
+use crate::errors::Error;
+use crate::ignores::Ignores;
+use crate::triggers::Triggers;
+use db::sea_orm::DbConn;
+use mrsbfh::config::Loader;
+use rand::{RngCore, SeedableRng};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Mutex;
+use tracing::*;
+
+/// Reloads the config file on SIGHUP and pushes it into the running bot
+pub struct ConfigReloader<T>
+where
+    T: RngCore + SeedableRng + Send,
+{
+    config_path: String,
+    config: Arc<Mutex<Config<'static>>>,
+    triggers: Arc<Mutex<Triggers>>,
+    ignores: Arc<Mutex<Ignores>>,
+    db: Arc<Mutex<DbConn>>,
+    dad_handler: Arc<Mutex<DaddedManager>>,
+    rng_handler: Arc<Mutex<RngManager<T>>>,
+}
+
+impl<T> ConfigReloader<T>
+where
+    T: RngCore + SeedableRng + Send,
+{
+    pub fn new(
+        config_path: String,
+        config: Arc<Mutex<Config<'static>>>,
+        triggers: Arc<Mutex<Triggers>>,
+        ignores: Arc<Mutex<Ignores>>,
+        db: Arc<Mutex<DbConn>>,
+        dad_handler: Arc<Mutex<DaddedManager>>,
+        rng_handler: Arc<Mutex<RngManager<T>>>,
+    ) -> Self {
+        Self {
+            config_path,
+            config,
+            triggers,
+            ignores,
+            db,
+            dad_handler,
+            rng_handler,
+        }
+    }
+
+    pub async fn watch_sighup(self) {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("Couldn't listen for SIGHUP, config reloading is off: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            info!("Got SIGHUP, reloading {}...", self.config_path);
+            if let Err(e) = self.reload().await {
+                error!("Rejected new config, keeping the old one: {}", e);
+            }
+        }
+    }
+
+    pub async fn reload(&self) -> Result<(), Error> {
+        let new_config =
+            Config::load(self.config_path.clone()).map_err(|e| Error::ConfigLoad(e.to_string()))?;
+        self.apply(new_config).await
+    }
+
+    /// Swaps in a new config, leaving everything untouched if it's invalid
+    pub async fn apply(&self, new_config: Config<'static>) -> Result<(), Error> {
+        let problems = find_problems(&new_config);
+        if !problems.is_empty() {
+            return Err(Error::InvalidConfig(problems.join("; ")));
+        }
+        let new_triggers = Triggers::from_config(&new_config)?;
+        let new_ignores = Ignores::from_config(&new_config)?;
+
+        let old_epoch_length = {
+            let config = &*self.config.lock().await;
+            warn_about_restart(config, &new_config);
+            config.epoch_length
+        };
+        if old_epoch_length != new_config.epoch_length {
+            let dad_handler = &mut *self.dad_handler.lock().await;
+            let db = &*self.db.lock().await;
+            dad_handler
+                .update_epoch_length(db, new_config.get_epoch_length())
+                .await?;
+        }
+        *self.triggers.lock().await = new_triggers;
+        self.ignores.lock().await.replace_config_lists(new_ignores);
+        self.rng_handler
+            .lock()
+            .await
+            .set_chances(new_config.dadded_chance, new_config.love_me_chance);
+        *self.config.lock().await = new_config;
+        info!("Reloaded config");
+        Ok(())
+    }
+}
+
+fn warn_about_restart(old: &Config, new: &Config) {
+    let restart_only = [
+        ("homeserver_url", old.homeserver_url != new.homeserver_url),
+        ("mxid", old.mxid != new.mxid),
+        ("password", old.password != new.password),
+        ("store_path", old.store_path != new.store_path),
+        ("session_path", old.session_path != new.session_path),
+        ("db", old.db != new.db),
+        ("backlog_grace", old.backlog_grace != new.backlog_grace),
+    ];
+    for (name, _) in restart_only.iter().filter(|(_, changed)| *changed) {
+        warn!("{} changed, it will only be used after a restart", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::utils::EnabledChance;
+    use crate::integration_utils::{create_inmemory_db, load_example_config, SeedableStepRng};
+    use chrono::{Duration, Local};
+    use db::utils::epochs;
+
+    async fn create_reloader() -> Result<ConfigReloader<SeedableStepRng>, Error> {
+        let config = load_example_config();
+        let db = create_inmemory_db().await?;
+        let epoch_length = config.get_epoch_length();
+        let epoch = epochs::get_or_create_epoch(&db, &Local::now(), epoch_length).await?;
+        let next_epoch = epochs::get_next_epoch_bound(&db, epoch.id, epoch_length).await?;
+        let triggers = Triggers::from_config(&config)?;
+        let ignores = Ignores::from_config(&config)?;
+        let rng = RngManager::new(
+            config.dadded_chance,
+            config.love_me_chance,
+            SeedableStepRng::new(0, 1),
+        );
+        Ok(ConfigReloader::new(
+            String::from("config.yml"),
+            Arc::new(Mutex::new(config)),
+            Arc::new(Mutex::new(triggers)),
+            Arc::new(Mutex::new(ignores)),
+            Arc::new(Mutex::new(db)),
+            Arc::new(Mutex::new(DaddedManager::new(epoch.id, next_epoch))),
+            Arc::new(Mutex::new(rng)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_apply_new_config() -> Result<(), Error> {
+        let reloader = create_reloader().await?;
+        let mut new_config = load_example_config();
+        new_config.dadded_chance = Some(10);
+        new_config.love_me_chance = Some(0);
+        new_config.epoch_length *= 2;
+        new_config.triggers = None;
+        new_config.ignored_rooms = Some(vec!["!noisy:example.org".into()]);
+
+        reloader.apply(new_config.clone()).await?;
+
+        assert_eq!(*reloader.config.lock().await, new_config);
+        let rng = reloader.rng_handler.lock().await;
+        assert_eq!(*rng.dadded_chance(), EnabledChance::In(10));
+        assert_eq!(*rng.love_me_chance(), EnabledChance::Off);
+        let dad_handler = reloader.dad_handler.lock().await;
+        let db = &*reloader.db.lock().await;
+        let next_epoch = epochs::get_next_epoch_bound(
+            db,
+            *dad_handler.epoch_id(),
+            Duration::minutes(new_config.epoch_length),
+        )
+        .await?;
+        assert_eq!(*dad_handler.next_epoch(), next_epoch);
+        let triggers = reloader.triggers.lock().await;
+        assert!(triggers.find("I feel hungry", None).is_none());
+        let ignores = reloader.ignores.lock().await;
+        assert!(ignores.is_ignored("!noisy:example.org", "@alice:example.org"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_invalid_config() -> Result<(), Error> {
+        let reloader = create_reloader().await?;
+        let old_config = reloader.config.lock().await.clone();
+        let mut bad_regex = old_config.clone();
+        bad_regex.dadded_regex = "(?P<dad_text>".into();
+        let mut bad_epoch = old_config.clone();
+        bad_epoch.epoch_length = 0;
+
+        assert!(reloader.apply(bad_regex).await.is_err());
+        assert!(reloader.apply(bad_epoch).await.is_err());
+
+        assert_eq!(*reloader.config.lock().await, old_config);
+        let triggers = reloader.triggers.lock().await;
+        assert!(triggers.find("I feel hungry", None).is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::commands::utils::{DaddedManager, EnabledChance, RngManager};
+use crate::config::Config;
+use crate::dadding::{DadAttempt, DadOutcome, Dadder};
+use crate::transport::{InMemoryTransport, IncomingMessage};
+use crate::triggers::Triggers;
+use chrono::Local;
+use db::migration::*;
+use db::sea_orm::*;
+use db::utils::epochs;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::*;
+
+fn describe_chance(chance: &EnabledChance) -> String {
+    match chance {
+        EnabledChance::Off => String::from("never"),
+        EnabledChance::Always => String::from("always"),
+        EnabledChance::In(c) => format!("1 in {}", c),
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn format_attempt(attempt: &DadAttempt) -> String {
+    let mut lines = Vec::new();
+    if let Some(trigger) = &attempt.trigger {
+        lines.push(format!("trigger:  {}", trigger));
+    }
+    if !attempt.captures.is_empty() {
+        let mut captures = attempt
+            .captures
+            .iter()
+            .map(|(name, value)| format!("{}={:?}", name, value))
+            .collect::<Vec<_>>();
+        captures.sort();
+        lines.push(format!("captures: {}", captures.join(" ")));
+    }
+    if let (Some(chance), Some(should_dad)) = (&attempt.dadded_chance, attempt.should_dad) {
+        lines.push(format!(
+            "roll:     {} -> {}",
+            describe_chance(chance),
+            yes_no(should_dad)
+        ));
+    }
+    if let Some(should_love) = attempt.should_love {
+        lines.push(format!("love:     {}", yes_no(should_love)));
+    }
+    let outcome = match &attempt.outcome {
+        DadOutcome::Dadded(reply) => format!("reply:    {}", reply.text),
+        DadOutcome::Disabled => String::from("no reply: dadding is disabled in this room"),
+        DadOutcome::OptedOut => String::from("no reply: sender opted out"),
+        DadOutcome::NoTrigger => String::from("no reply: no trigger matched"),
+        DadOutcome::CoolingDown(cooldown) => format!("no reply: {:?} cooldown", cooldown),
+        DadOutcome::Unlucky => String::from("no reply: the dice said no"),
+    };
+    lines.push(outcome);
+    lines.join("\n")
+}
+
+/// Runs chat lines from stdin through the dadding pipeline against a local database
+pub async fn run(
+    config: Config<'static>,
+    db_conn_str: &str,
+    room_id: String,
+    sender: String,
+    seed: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    let db = Database::connect(db_conn_str).await?;
+    Migrator::up(&db, None).await?;
+    let epoch_length = config.get_epoch_length();
+    let epoch = epochs::get_or_create_epoch(&db, &Local::now(), epoch_length).await?;
+    let next_epoch = epochs::get_next_epoch_bound(&db, epoch.id, epoch_length).await?;
+    let rng = match seed {
+        Some(seed) => ChaChaRng::seed_from_u64(seed),
+        None => ChaChaRng::from_entropy(),
+    };
+    let rng_manager = RngManager::new(config.dadded_chance, config.love_me_chance, rng);
+    let triggers = Triggers::from_config(&config)?;
+    let dadder = Dadder::new(
+        Arc::new(Mutex::new(config)),
+        Arc::new(Mutex::new(db)),
+        Arc::new(Mutex::new(DaddedManager::new(epoch.id, next_epoch))),
+        Arc::new(Mutex::new(rng_manager)),
+        Arc::new(Mutex::new(triggers)),
+    );
+    let transport = InMemoryTransport::new("@dad:localhost");
+
+    println!("Talking as {} in {}, Ctrl-D to stop", sender, room_id);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut lines_read = 0;
+    let mut count = 0;
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let msg = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let event_id = format!("$repl-{}", lines_read);
+        lines_read += 1;
+        let message = IncomingMessage::text(&room_id, &sender, &event_id, &msg);
+        if let Some(attempt) = dadder.handle_message(&transport, &message).await? {
+            println!("{}", format_attempt(&attempt));
+            if attempt.reply().is_some() {
+                count += 1;
+            }
+        }
+    }
+    info!("Dadded {} times this session", count);
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::utils::Cooldown;
+    use crate::dadding::DadReply;
+    use crate::errors::Error;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_format_dadded_attempt() -> Result<(), Error> {
+        let mut captures = HashMap::new();
+        captures.insert(String::from("im"), String::from("I'm"));
+        captures.insert(String::from("dad_text"), String::from("hungry"));
+        let attempt = DadAttempt {
+            outcome: DadOutcome::Dadded(DadReply {
+                text: String::from("Hi hungry! I'm Dad!"),
+                dad_text: String::from("hungry"),
+                loved: false,
+                template: String::from("Hi {dad_text}! I'm Dad!"),
+            }),
+            trigger: Some(String::from("I'm (?P<dad_text>.+)")),
+            captures,
+            dadded_chance: Some(EnabledChance::In(2)),
+            should_dad: Some(true),
+            should_love: Some(false),
+        };
+        let expected = "trigger:  I'm (?P<dad_text>.+)\n\
+            captures: dad_text=\"hungry\" im=\"I'm\"\n\
+            roll:     1 in 2 -> yes\n\
+            love:     no\n\
+            reply:    Hi hungry! I'm Dad!";
+        assert_eq!(format_attempt(&attempt), expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_format_cooling_down_attempt() -> Result<(), Error> {
+        let attempt = DadAttempt {
+            outcome: DadOutcome::CoolingDown(Cooldown::Room),
+            trigger: None,
+            captures: HashMap::new(),
+            dadded_chance: None,
+            should_dad: None,
+            should_love: None,
+        };
+        assert_eq!(format_attempt(&attempt), "no reply: Room cooldown");
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use super::{Mention, MessageKind, OutgoingMessage, ReplyTo, Transport};
+use crate::errors::Error;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SentEvent {
+    Message {
+        room_id: String,
+        event_id: String,
+        kind: MessageKind,
+        body: String,
+        reply_to: Option<ReplyTo>,
+        mention: Option<Mention>,
+    },
+    Edit {
+        room_id: String,
+        event_id: String,
+        replaces: String,
+        kind: MessageKind,
+        body: String,
+        mention: Option<Mention>,
+    },
+    Reaction {
+        room_id: String,
+        event_id: String,
+        relates_to: String,
+        key: String,
+    },
+}
+
+/// A transport that keeps everything in memory, for tests and the REPL
+pub struct InMemoryTransport {
+    own_user_id: String,
+    sent: Mutex<Vec<SentEvent>>,
+    display_names: Mutex<HashMap<(String, String), String>>,
+    room_names: Mutex<HashMap<String, String>>,
+    power_levels: Mutex<HashMap<(String, String), i64>>,
+    members: Mutex<HashMap<String, HashSet<String>>>,
+    // Rooms whose joins fail, like rooms the homeserver doesn't know about yet
+    unjoinable: Mutex<HashSet<String>>,
+}
+
+impl InMemoryTransport {
+    pub fn new(own_user_id: &str) -> Self {
+        Self {
+            own_user_id: own_user_id.to_string(),
+            sent: Mutex::new(Vec::new()),
+            display_names: Mutex::new(HashMap::new()),
+            room_names: Mutex::new(HashMap::new()),
+            power_levels: Mutex::new(HashMap::new()),
+            members: Mutex::new(HashMap::new()),
+            unjoinable: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn sent(&self) -> Vec<SentEvent> {
+        self.sent.lock().await.clone()
+    }
+
+    #[cfg(test)]
+    pub async fn set_display_name(&self, room_id: &str, user_id: &str, name: &str) {
+        self.display_names
+            .lock()
+            .await
+            .insert((room_id.to_string(), user_id.to_string()), name.to_string());
+    }
+
+    #[cfg(test)]
+    pub async fn set_room_name(&self, room_id: &str, name: &str) {
+        self.room_names
+            .lock()
+            .await
+            .insert(room_id.to_string(), name.to_string());
+    }
+
+    #[cfg(test)]
+    pub async fn set_power_level(&self, room_id: &str, user_id: &str, power_level: i64) {
+        self.power_levels
+            .lock()
+            .await
+            .insert((room_id.to_string(), user_id.to_string()), power_level);
+    }
+
+    #[cfg(test)]
+    pub async fn add_member(&self, room_id: &str, user_id: &str) {
+        self.members
+            .lock()
+            .await
+            .entry(room_id.to_string())
+            .or_default()
+            .insert(user_id.to_string());
+    }
+
+    #[cfg(test)]
+    pub async fn is_member(&self, room_id: &str, user_id: &str) -> bool {
+        self.members
+            .lock()
+            .await
+            .get(room_id)
+            .map_or(false, |members| members.contains(user_id))
+    }
+
+    #[cfg(test)]
+    pub async fn set_unjoinable(&self, room_id: &str) {
+        self.unjoinable.lock().await.insert(room_id.to_string());
+    }
+
+    async fn next_event_id(&self, event: impl FnOnce(String) -> SentEvent) -> String {
+        let sent = &mut *self.sent.lock().await;
+        let event_id = format!("$sent-{}", sent.len() + 1);
+        sent.push(event(event_id.clone()));
+        event_id
+    }
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    fn own_user_id(&self) -> &str {
+        &self.own_user_id
+    }
+
+    async fn send_message(
+        &self,
+        room_id: &str,
+        message: &OutgoingMessage,
+    ) -> Result<String, Error> {
+        let event_id = self
+            .next_event_id(|event_id| SentEvent::Message {
+                room_id: room_id.to_string(),
+                event_id,
+                kind: message.kind,
+                body: message.markdown.clone(),
+                reply_to: message.reply_to.clone(),
+                mention: message.mention.clone(),
+            })
+            .await;
+        Ok(event_id)
+    }
+
+    async fn edit_message(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        message: &OutgoingMessage,
+    ) -> Result<String, Error> {
+        let edit_id = self
+            .next_event_id(|edit_id| SentEvent::Edit {
+                room_id: room_id.to_string(),
+                event_id: edit_id,
+                replaces: event_id.to_string(),
+                kind: message.kind,
+                body: message.markdown.clone(),
+                mention: message.mention.clone(),
+            })
+            .await;
+        Ok(edit_id)
+    }
+
+    async fn send_reaction(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        key: &str,
+    ) -> Result<String, Error> {
+        let reaction_id = self
+            .next_event_id(|reaction_id| SentEvent::Reaction {
+                room_id: room_id.to_string(),
+                event_id: reaction_id,
+                relates_to: event_id.to_string(),
+                key: key.to_string(),
+            })
+            .await;
+        Ok(reaction_id)
+    }
+
+    async fn display_name(&self, room_id: &str, user_id: &str) -> Option<String> {
+        self.display_names
+            .lock()
+            .await
+            .get(&(room_id.to_string(), user_id.to_string()))
+            .cloned()
+    }
+
+    async fn room_name(&self, room_id: &str) -> Option<String> {
+        self.room_names.lock().await.get(room_id).cloned()
+    }
+
+    async fn power_level(&self, room_id: &str, user_id: &str) -> i64 {
+        self.power_levels
+            .lock()
+            .await
+            .get(&(room_id.to_string(), user_id.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    async fn member_count(&self, room_id: &str) -> Option<u64> {
+        self.members
+            .lock()
+            .await
+            .get(room_id)
+            .map(|members| members.len() as u64)
+    }
+
+    async fn join_room(&self, room_id: &str) -> Result<(), Error> {
+        if self.unjoinable.lock().await.contains(room_id) {
+            return Err(Error::Transport(format!("Can't join {}", room_id)));
+        }
+        self.members
+            .lock()
+            .await
+            .entry(room_id.to_string())
+            .or_default()
+            .insert(self.own_user_id.clone());
+        Ok(())
+    }
+
+    async fn leave_room(&self, room_id: &str) -> Result<(), Error> {
+        if let Some(members) = self.members.lock().await.get_mut(room_id) {
+            members.remove(&self.own_user_id);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sent_events_are_recorded() -> Result<(), Error> {
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        let text_id = transport.send_text(room_id, "Hi hungry!").await?;
+        let reaction_id = transport.send_reaction(room_id, &text_id, "👋").await?;
+        assert_ne!(text_id, reaction_id);
+        assert_eq!(
+            transport.sent().await,
+            vec![
+                SentEvent::Message {
+                    room_id: room_id.to_string(),
+                    event_id: text_id.clone(),
+                    kind: MessageKind::Text,
+                    body: String::from("Hi hungry!"),
+                    reply_to: None,
+                    mention: None,
+                },
+                SentEvent::Reaction {
+                    room_id: room_id.to_string(),
+                    event_id: reaction_id,
+                    relates_to: text_id,
+                    key: String::from("👋"),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_membership() -> Result<(), Error> {
+        let transport = InMemoryTransport::new("@dad:example.org");
+        let room_id = "!room:example.org";
+        assert_eq!(transport.member_count(room_id).await, None);
+        transport.add_member(room_id, "@alice:example.org").await;
+        transport.join_room(room_id).await?;
+        assert_eq!(transport.member_count(room_id).await, Some(2));
+        assert!(transport.is_member(room_id, "@dad:example.org").await);
+        transport.leave_room(room_id).await?;
+        assert_eq!(transport.member_count(room_id).await, Some(1));
+        assert!(!transport.is_member(room_id, "@dad:example.org").await);
+        Ok(())
+    }
+}
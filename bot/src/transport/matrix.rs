@@ -0,0 +1,633 @@
+use super::{
+    IncomingMessage, Membership, MembershipChange, Mention, MessageKind, OutgoingMessage, ReplyTo,
+    Transport,
+};
+use crate::errors::Error;
+use async_trait::async_trait;
+use matrix_sdk::{
+    event_handler::RawEvent,
+    room::{Joined, Room},
+    ruma::{
+        events::{
+            reaction::{ReactionEventContent, Relation},
+            room::{
+                member::{MemberEventContent, MembershipState},
+                message::{
+                    EmoteMessageEventContent, MessageEventContent, MessageType,
+                    NoticeMessageEventContent, TextMessageEventContent,
+                },
+            },
+            AnyMessageEventContent, StrippedStateEvent, SyncMessageEvent,
+        },
+        EventId, RoomId, UserId,
+    },
+    Client,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use tracing::*;
+
+// Threads and edits aren't fully known to this version of ruma, so relations are read from the raw event
+const THREAD_REL_TYPES: [&str; 2] = ["m.thread", "io.element.thread"];
+const REPLACE_REL_TYPE: &str = "m.replace";
+const MATRIX_TO: &str = "https://matrix.to/#/";
+
+#[derive(Deserialize)]
+struct RawInReplyTo {}
+
+#[derive(Deserialize)]
+struct RawRelatesTo {
+    rel_type: Option<String>,
+    event_id: Option<String>,
+    #[serde(rename = "m.in_reply_to")]
+    in_reply_to: Option<RawInReplyTo>,
+}
+
+#[derive(Deserialize)]
+struct RawNewContent {
+    body: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawContent {
+    #[serde(rename = "m.relates_to")]
+    relates_to: Option<RawRelatesTo>,
+    #[serde(rename = "m.new_content")]
+    new_content: Option<RawNewContent>,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    content: RawContent,
+}
+
+impl RawMessage {
+    fn parse(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    /// The related event if the relation is one of `rel_types`
+    fn related_event(&self, rel_types: &[&str]) -> Option<String> {
+        let relates_to = self.content.relates_to.as_ref()?;
+        match &relates_to.rel_type {
+            Some(rel_type) if rel_types.contains(&rel_type.as_str()) => relates_to.event_id.clone(),
+            _ => None,
+        }
+    }
+
+    fn is_reply(&self) -> bool {
+        self.content
+            .relates_to
+            .as_ref()
+            .map_or(false, |relates_to| relates_to.in_reply_to.is_some())
+    }
+
+    fn new_body(&self) -> Option<String> {
+        self.content.new_content.as_ref()?.body.clone()
+    }
+}
+
+/// Removes the `> <@alice:example.org> I'm hungry` quote clients put in front of replies.
+/// Only the plain body is matched, so the `<mx-reply>` of formatted bodies is never read
+fn strip_reply_fallback(body: &str) -> &str {
+    let mut rest = body;
+    while rest.starts_with('>') {
+        rest = match rest.find('\n') {
+            Some(end) => &rest[end + 1..],
+            None => "",
+        };
+    }
+    rest.strip_prefix('\n').unwrap_or(rest)
+}
+
+/// Drops the backslashes escaping punctuation, for plain bodies built from markdown
+fn unescape_markdown(markdown: &str) -> String {
+    let mut plain = String::with_capacity(markdown.len());
+    let mut chars = markdown.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '\\' && next.is_ascii_punctuation() => {
+                plain.push(*next);
+                chars.next();
+            }
+            _ => plain.push(c),
+        }
+    }
+    plain
+}
+
+/// The body shown by clients without HTML, the same for new messages and edit fallbacks
+fn get_plain_body(markdown: &str, mention: Option<&Mention>) -> String {
+    let body = unescape_markdown(markdown);
+    match mention {
+        Some(mention) => format!("{}: {}", unescape_markdown(&mention.display_name), body),
+        None => body,
+    }
+}
+
+fn get_message_content(
+    kind: MessageKind,
+    markdown: &str,
+    mention: Option<&Mention>,
+) -> MessageEventContent {
+    let plain_body = get_plain_body(markdown, mention);
+    let markdown = match mention {
+        // The pill is a link to the user, which plain bodies would show as markdown
+        Some(mention) => format!(
+            "[{}]({}{}): {}",
+            mention.display_name, MATRIX_TO, mention.user_id, markdown
+        ),
+        None => markdown.to_string(),
+    };
+    let mut msgtype = match kind {
+        MessageKind::Text => MessageType::Text(TextMessageEventContent::markdown(markdown)),
+        MessageKind::Emote => MessageType::Emote(EmoteMessageEventContent::markdown(markdown)),
+        MessageKind::Notice => MessageType::Notice(NoticeMessageEventContent::markdown(markdown)),
+    };
+    match &mut msgtype {
+        MessageType::Text(content) => content.body = plain_body,
+        MessageType::Emote(content) => content.body = plain_body,
+        MessageType::Notice(content) => content.body = plain_body,
+        _ => (),
+    }
+    MessageEventContent::new(msgtype)
+}
+
+fn get_plain_content(kind: MessageKind, body: String) -> MessageEventContent {
+    MessageEventContent::new(match kind {
+        MessageKind::Text => MessageType::Text(TextMessageEventContent::plain(body)),
+        MessageKind::Emote => MessageType::Emote(EmoteMessageEventContent::plain(body)),
+        MessageKind::Notice => MessageType::Notice(NoticeMessageEventContent::plain(body)),
+    })
+}
+
+fn get_edit_content(message: &OutgoingMessage, event_id: &str) -> Result<Value, Error> {
+    let new_content =
+        get_message_content(message.kind, &message.markdown, message.mention.as_ref());
+    // Clients without edits show the fallback, plain so the `*` isn't made into a list
+    let fallback = get_plain_content(
+        message.kind,
+        format!(
+            "* {}",
+            get_plain_body(&message.markdown, message.mention.as_ref())
+        ),
+    );
+    let mut content =
+        serde_json::to_value(fallback).map_err(|e| Error::Transport(e.to_string()))?;
+    content["m.new_content"] =
+        serde_json::to_value(new_content).map_err(|e| Error::Transport(e.to_string()))?;
+    content["m.relates_to"] = json!({ "rel_type": REPLACE_REL_TYPE, "event_id": event_id });
+    Ok(content)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\n' => escaped.push_str("<br />"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Puts the quote of the answered message in front of a reply, clients without rich replies show
+/// it instead of the link to the trigger
+fn add_reply_fallback(content: &mut Value, room_id: &str, reply_to: &ReplyTo) {
+    let quote = reply_to
+        .body
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| match idx {
+            0 => format!("> <{}> {}", reply_to.sender, line),
+            _ => format!("> {}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = content["body"].as_str().unwrap_or_default().to_string();
+    let formatted_body = match content["formatted_body"].as_str() {
+        Some(formatted_body) => formatted_body.to_string(),
+        None => escape_html(&body),
+    };
+    content["body"] = json!(format!("{}\n\n{}", quote, body));
+    content["format"] = json!("org.matrix.custom.html");
+    content["formatted_body"] = json!(format!(
+        "<mx-reply><blockquote><a href=\"{to}{room_id}/{event_id}\">In reply to</a> \
+        <a href=\"{to}{sender}\">{sender}</a><br />{quoted}</blockquote></mx-reply>{formatted_body}",
+        to = MATRIX_TO,
+        room_id = room_id,
+        event_id = reply_to.event_id,
+        sender = reply_to.sender,
+        quoted = escape_html(&reply_to.body),
+        formatted_body = formatted_body
+    ));
+}
+
+fn get_relates_to(reply_to: &ReplyTo) -> Value {
+    let in_reply_to = json!({ "event_id": reply_to.event_id });
+    match &reply_to.thread_root {
+        // Clients without threads show the reply, so it only counts as a real one when quoting
+        Some(thread_root) => json!({
+            "rel_type": THREAD_REL_TYPES[0],
+            "event_id": thread_root,
+            "is_falling_back": !reply_to.quote,
+            "m.in_reply_to": in_reply_to,
+        }),
+        None => json!({ "m.in_reply_to": in_reply_to }),
+    }
+}
+
+/// Converts a room message into an [`IncomingMessage`], ignoring message types the bot can't read
+pub fn message_from_event(
+    event: &SyncMessageEvent<MessageEventContent>,
+    room: &Room,
+    raw: &RawEvent,
+) -> Option<IncomingMessage> {
+    let raw = RawMessage::parse(raw.0.get());
+    let (kind, body) = match &event.content.msgtype {
+        MessageType::Text(TextMessageEventContent { body, .. }) => (MessageKind::Text, body),
+        MessageType::Emote(EmoteMessageEventContent { body, .. }) => (MessageKind::Emote, body),
+        MessageType::Notice(NoticeMessageEventContent { body, .. }) => (MessageKind::Notice, body),
+        _ => return None,
+    };
+    let replaces = raw
+        .as_ref()
+        .and_then(|raw| raw.related_event(&[REPLACE_REL_TYPE]));
+    let body = match raw.as_ref().and_then(RawMessage::new_body) {
+        Some(new_body) if replaces.is_some() => new_body,
+        _ if raw.as_ref().map_or(false, RawMessage::is_reply) => {
+            strip_reply_fallback(body).to_string()
+        }
+        _ => body.clone(),
+    };
+    Some(IncomingMessage {
+        room_id: room.room_id().to_string(),
+        sender: event.sender.to_string(),
+        event_id: event.event_id.to_string(),
+        kind,
+        body,
+        thread_root: raw
+            .as_ref()
+            .and_then(|raw| raw.related_event(&THREAD_REL_TYPES)),
+        replaces,
+    })
+}
+
+/// Converts a stripped member event, which is how invites arrive, into a [`MembershipChange`]
+pub fn membership_from_event(
+    event: &StrippedStateEvent<MemberEventContent>,
+    room: &Room,
+) -> Option<MembershipChange> {
+    let membership = match event.content.membership {
+        MembershipState::Invite => Membership::Invite,
+        MembershipState::Join => Membership::Join,
+        MembershipState::Leave | MembershipState::Ban => Membership::Leave,
+        _ => return None,
+    };
+    Some(MembershipChange {
+        room_id: room.room_id().to_string(),
+        user_id: event.state_key.clone(),
+        sender: event.sender.to_string(),
+        membership,
+        is_direct: event.content.is_direct.unwrap_or(false),
+    })
+}
+
+fn parse_room_id(room_id: &str) -> Result<RoomId, Error> {
+    RoomId::try_from(room_id).map_err(|e| Error::Transport(format!("{}: {}", room_id, e)))
+}
+
+pub struct MatrixTransport {
+    client: Client,
+    own_user_id: String,
+}
+
+impl MatrixTransport {
+    pub fn new(client: Client, own_user_id: String) -> Self {
+        Self {
+            client,
+            own_user_id,
+        }
+    }
+
+    fn get_room(&self, room_id: &str) -> Option<Room> {
+        let room_id = parse_room_id(room_id).ok()?;
+        self.client.get_room(&room_id)
+    }
+
+    fn get_joined_room(&self, room_id: &str) -> Result<Joined, Error> {
+        self.client
+            .get_joined_room(&parse_room_id(room_id)?)
+            .ok_or_else(|| Error::Transport(format!("Not joined to {}", room_id)))
+    }
+
+    async fn send(&self, room_id: &str, content: AnyMessageEventContent) -> Result<String, Error> {
+        let room = self.get_joined_room(room_id)?;
+        let response = room.send(content, None).await?;
+        Ok(response.event_id.to_string())
+    }
+}
+
+#[async_trait]
+impl Transport for MatrixTransport {
+    fn own_user_id(&self) -> &str {
+        &self.own_user_id
+    }
+
+    async fn send_message(
+        &self,
+        room_id: &str,
+        message: &OutgoingMessage,
+    ) -> Result<String, Error> {
+        let content =
+            get_message_content(message.kind, &message.markdown, message.mention.as_ref());
+        if message.reply_to.is_none() && message.mention.is_none() {
+            return self
+                .send(room_id, AnyMessageEventContent::RoomMessage(content))
+                .await;
+        }
+        let room = self.get_joined_room(room_id)?;
+        let mut content =
+            serde_json::to_value(content).map_err(|e| Error::Transport(e.to_string()))?;
+        if let Some(reply_to) = &message.reply_to {
+            if reply_to.quote {
+                add_reply_fallback(&mut content, room_id, reply_to);
+            }
+            content["m.relates_to"] = get_relates_to(reply_to);
+        }
+        if let Some(mention) = &message.mention {
+            content["m.mentions"] = json!({ "user_ids": [mention.user_id] });
+        }
+        let response = room.send_raw(content, "m.room.message", None).await?;
+        Ok(response.event_id.to_string())
+    }
+
+    async fn edit_message(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        message: &OutgoingMessage,
+    ) -> Result<String, Error> {
+        let room = self.get_joined_room(room_id)?;
+        let content = get_edit_content(message, event_id)?;
+        let response = room.send_raw(content, "m.room.message", None).await?;
+        Ok(response.event_id.to_string())
+    }
+
+    async fn send_reaction(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        key: &str,
+    ) -> Result<String, Error> {
+        let event_id = EventId::try_from(event_id)
+            .map_err(|e| Error::Transport(format!("{}: {}", event_id, e)))?;
+        let content = AnyMessageEventContent::Reaction(ReactionEventContent::new(Relation::new(
+            event_id,
+            key.to_string(),
+        )));
+        self.send(room_id, content).await
+    }
+
+    async fn display_name(&self, room_id: &str, user_id: &str) -> Option<String> {
+        let room = self.get_room(room_id)?;
+        let user_id = UserId::try_from(user_id).ok()?;
+        match room.get_member(&user_id).await {
+            Ok(Some(member)) => Some(member.name().to_string()),
+            _ => None,
+        }
+    }
+
+    async fn room_name(&self, room_id: &str) -> Option<String> {
+        self.get_room(room_id)?.display_name().await.ok()
+    }
+
+    async fn power_level(&self, room_id: &str, user_id: &str) -> i64 {
+        let room = match self.get_room(room_id) {
+            Some(room) => room,
+            None => return 0,
+        };
+        let user_id = match UserId::try_from(user_id) {
+            Ok(user_id) => user_id,
+            Err(_) => return 0,
+        };
+        match room.get_member(&user_id).await {
+            Ok(Some(member)) => member.power_level(),
+            Ok(None) => 0,
+            Err(e) => {
+                error!("Error getting power level of {}: {}", user_id, e);
+                0
+            }
+        }
+    }
+
+    async fn member_count(&self, room_id: &str) -> Option<u64> {
+        Some(self.get_room(room_id)?.joined_members_count())
+    }
+
+    async fn join_room(&self, room_id: &str) -> Result<(), Error> {
+        self.client
+            .join_room_by_id(&parse_room_id(room_id)?)
+            .await?;
+        Ok(())
+    }
+
+    async fn leave_room(&self, room_id: &str) -> Result<(), Error> {
+        match self.get_room(room_id) {
+            Some(Room::Joined(room)) => room.leave().await?,
+            Some(Room::Invited(room)) => room.reject_invitation().await?,
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_thread_root(json: &str) -> Option<String> {
+        RawMessage::parse(json)?.related_event(&THREAD_REL_TYPES)
+    }
+
+    #[tokio::test]
+    async fn test_thread_root() -> Result<(), Error> {
+        let threaded = r#"{"content": {"body": "I'm hungry", "msgtype": "m.text",
+            "m.relates_to": {"rel_type": "m.thread", "event_id": "$root"}}}"#;
+        assert_eq!(get_thread_root(threaded), Some(String::from("$root")));
+        let unstable = r#"{"content": {"body": "I'm hungry", "msgtype": "m.text",
+            "m.relates_to": {"rel_type": "io.element.thread", "event_id": "$root"}}}"#;
+        assert_eq!(get_thread_root(unstable), Some(String::from("$root")));
+        let reply = r#"{"content": {"body": "I'm hungry", "msgtype": "m.text",
+            "m.relates_to": {"m.in_reply_to": {"event_id": "$other"}}}}"#;
+        assert_eq!(get_thread_root(reply), None);
+        let plain = r#"{"content": {"body": "I'm hungry", "msgtype": "m.text"}}"#;
+        assert_eq!(get_thread_root(plain), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relates_to() -> Result<(), Error> {
+        let mut reply_to = ReplyTo {
+            event_id: String::from("$trigger"),
+            sender: String::from("@alice:example.org"),
+            body: String::from("I'm hungry"),
+            quote: true,
+            thread_root: None,
+        };
+        assert_eq!(
+            get_relates_to(&reply_to),
+            json!({ "m.in_reply_to": { "event_id": "$trigger" } })
+        );
+        reply_to.quote = false;
+        reply_to.thread_root = Some(String::from("$root"));
+        assert_eq!(
+            get_relates_to(&reply_to),
+            json!({
+                "rel_type": "m.thread",
+                "event_id": "$root",
+                "is_falling_back": true,
+                "m.in_reply_to": { "event_id": "$trigger" },
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_reply_fallback() -> Result<(), Error> {
+        let reply_to = ReplyTo {
+            event_id: String::from("$trigger"),
+            sender: String::from("@alice:example.org"),
+            body: String::from("I'm <hungry>\nreally"),
+            quote: true,
+            thread_root: None,
+        };
+        let content = get_message_content(MessageKind::Text, "Hi hungry!", None);
+        let mut content = serde_json::to_value(content).unwrap();
+        add_reply_fallback(&mut content, "!room:example.org", &reply_to);
+        assert_eq!(
+            content["body"],
+            "> <@alice:example.org> I'm <hungry>\n> really\n\nHi hungry!"
+        );
+        assert_eq!(content["format"], "org.matrix.custom.html");
+        assert_eq!(
+            content["formatted_body"],
+            "<mx-reply><blockquote>\
+            <a href=\"https://matrix.to/#/!room:example.org/$trigger\">In reply to</a> \
+            <a href=\"https://matrix.to/#/@alice:example.org\">@alice:example.org</a>\
+            <br />I&#39;m &lt;hungry&gt;<br />really</blockquote></mx-reply>Hi hungry!"
+        );
+        // The fallback is stripped again when reading replies
+        assert_eq!(
+            strip_reply_fallback(content["body"].as_str().unwrap()),
+            "Hi hungry!"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit() -> Result<(), Error> {
+        let edit = RawMessage::parse(
+            r#"{"content": {"body": "* I'm thirsty", "msgtype": "m.text",
+            "m.new_content": {"body": "I'm thirsty", "msgtype": "m.text"},
+            "m.relates_to": {"rel_type": "m.replace", "event_id": "$original"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            edit.related_event(&[REPLACE_REL_TYPE]),
+            Some(String::from("$original"))
+        );
+        assert_eq!(edit.related_event(&THREAD_REL_TYPES), None);
+        assert_eq!(edit.new_body(), Some(String::from("I'm thirsty")));
+
+        let content = get_edit_content(&OutgoingMessage::text("Hi \\*thirsty\\*!"), "$reply")?;
+        assert_eq!(content["body"], "* Hi *thirsty*!");
+        assert_eq!(content["m.new_content"]["body"], "Hi *thirsty*!");
+        assert_eq!(
+            content["m.relates_to"],
+            json!({ "rel_type": "m.replace", "event_id": "$reply" })
+        );
+
+        let emote = OutgoingMessage {
+            kind: MessageKind::Emote,
+            markdown: String::from("says: Hi thirsty!"),
+            reply_to: None,
+            mention: Some(Mention {
+                user_id: String::from("@alice:example.org"),
+                display_name: String::from("Alice"),
+            }),
+        };
+        let content = get_edit_content(&emote, "$reply")?;
+        assert_eq!(content["msgtype"], "m.emote");
+        assert_eq!(content["body"], "* Alice: says: Hi thirsty!");
+        assert_eq!(content["m.new_content"]["msgtype"], "m.emote");
+        assert!(content["m.new_content"]["formatted_body"]
+            .as_str()
+            .unwrap()
+            .contains("https://matrix.to/#/@alice:example.org"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reply_fallback() -> Result<(), Error> {
+        let reply = RawMessage::parse(
+            r#"{"content": {"body": "> <@bob:example.org> I'm hungry\n\nHi hungry", "msgtype": "m.text",
+            "m.relates_to": {"m.in_reply_to": {"event_id": "$bob"}}}}"#,
+        )
+        .unwrap();
+        assert!(reply.is_reply());
+        assert_eq!(
+            strip_reply_fallback("> <@bob:example.org> I'm hungry\n\nHi hungry"),
+            "Hi hungry"
+        );
+        assert_eq!(
+            strip_reply_fallback("> <@bob:example.org> I'm hungry\n> and tired\n\nI'm not"),
+            "I'm not"
+        );
+        assert_eq!(strip_reply_fallback("> <@bob:example.org> I'm hungry"), "");
+        assert_eq!(
+            strip_reply_fallback("I'm hungry\n> quote"),
+            "I'm hungry\n> quote"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mention() -> Result<(), Error> {
+        assert_eq!(unescape_markdown("\\<b\\> \\\\ a\\b"), "<b> \\ a\\b");
+        let mention = Mention {
+            user_id: String::from("@alice:example.org"),
+            display_name: String::from("\\*Alice\\*"),
+        };
+        let content = get_message_content(MessageKind::Text, "Hi hungry!", Some(&mention));
+        let content = serde_json::to_value(content).map_err(|e| Error::Transport(e.to_string()))?;
+        assert_eq!(content["body"], "*Alice*: Hi hungry!");
+        let pill = "<a href=\"https://matrix.to/#/@alice:example.org\">*Alice*</a>: Hi hungry!";
+        assert!(content["formatted_body"].as_str().unwrap().contains(pill));
+
+        // New messages and edits show clients without HTML the same text
+        let message = OutgoingMessage {
+            kind: MessageKind::Text,
+            markdown: String::from("Hi \\*hungry\\*!"),
+            reply_to: None,
+            mention: Some(mention),
+        };
+        let content =
+            get_message_content(message.kind, &message.markdown, message.mention.as_ref());
+        let content = serde_json::to_value(content).map_err(|e| Error::Transport(e.to_string()))?;
+        assert_eq!(content["body"], "*Alice*: Hi *hungry*!");
+        let edit = get_edit_content(&message, "$reply")?;
+        assert_eq!(
+            edit["body"],
+            format!("* {}", content["body"].as_str().unwrap())
+        );
+        assert_eq!(edit["m.new_content"]["body"], content["body"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use crate::errors::Error;
+use async_trait::async_trait;
+
+pub use self::in_memory::{InMemoryTransport, SentEvent};
+pub use self::matrix::{membership_from_event, message_from_event, MatrixTransport};
+mod in_memory;
+mod matrix;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    Text,
+    Emote,
+    Notice,
+}
+
+/// A chat message as the bot sees it, whatever network it arrived on
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomingMessage {
+    pub room_id: String,
+    pub sender: String,
+    pub event_id: String,
+    pub kind: MessageKind,
+    pub body: String,
+    // Root event of the thread the message was sent in
+    pub thread_root: Option<String>,
+    // Event this message edits, `body` is then the new text without the fallback
+    pub replaces: Option<String>,
+}
+
+impl IncomingMessage {
+    pub fn text(room_id: &str, sender: &str, event_id: &str, body: &str) -> Self {
+        Self {
+            room_id: room_id.to_string(),
+            sender: sender.to_string(),
+            event_id: event_id.to_string(),
+            kind: MessageKind::Text,
+            body: body.to_string(),
+            thread_root: None,
+            replaces: None,
+        }
+    }
+
+    /// How an answer to this message should relate to it, `None` for a plain message
+    pub fn reply_to(&self, quote: bool) -> Option<ReplyTo> {
+        if !quote && self.thread_root.is_none() {
+            return None;
+        }
+        Some(ReplyTo {
+            event_id: self.event_id.clone(),
+            sender: self.sender.clone(),
+            body: self.body.clone(),
+            quote,
+            thread_root: self.thread_root.clone(),
+        })
+    }
+}
+
+/// The event a message answers, shown as a quote when `quote` is set and
+/// kept in the same thread when there is one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplyTo {
+    pub event_id: String,
+    // Who sent the answered message and what it said, for clients that can't show replies
+    pub sender: String,
+    pub body: String,
+    pub quote: bool,
+    pub thread_root: Option<String>,
+}
+
+/// Someone a message starts by addressing, `display_name` is markdown that's safe to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mention {
+    pub user_id: String,
+    pub display_name: String,
+}
+
+/// A markdown message for the bot to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingMessage {
+    pub kind: MessageKind,
+    pub markdown: String,
+    pub reply_to: Option<ReplyTo>,
+    pub mention: Option<Mention>,
+}
+
+impl OutgoingMessage {
+    pub fn text(markdown: &str) -> Self {
+        Self {
+            kind: MessageKind::Text,
+            markdown: markdown.to_string(),
+            reply_to: None,
+            mention: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Membership {
+    Invite,
+    Join,
+    Leave,
+}
+
+/// Someone's membership of a room changed, `sender` is who changed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MembershipChange {
+    pub room_id: String,
+    pub user_id: String,
+    pub sender: String,
+    pub membership: Membership,
+    // The sender marked the room as a direct message
+    pub is_direct: bool,
+}
+
+/// Everything the bot needs from the chat network it's running on
+#[async_trait]
+pub trait Transport: Send + Sync {
+    fn own_user_id(&self) -> &str;
+
+    /// Sends a markdown message, returning the ID of the new event
+    async fn send_text(&self, room_id: &str, markdown: &str) -> Result<String, Error> {
+        self.send_message(room_id, &OutgoingMessage::text(markdown))
+            .await
+    }
+
+    /// Sends a message of any kind, returning the ID of the new event
+    async fn send_message(&self, room_id: &str, message: &OutgoingMessage)
+        -> Result<String, Error>;
+
+    /// Replaces a message sent earlier with `message`, returning the ID of the edit.
+    /// `reply_to` is ignored since an edit can't change what a message answers
+    async fn edit_message(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        message: &OutgoingMessage,
+    ) -> Result<String, Error>;
+
+    /// Reacts to an event with `key`, returning the ID of the reaction
+    async fn send_reaction(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        key: &str,
+    ) -> Result<String, Error>;
+
+    async fn display_name(&self, room_id: &str, user_id: &str) -> Option<String>;
+
+    async fn room_name(&self, room_id: &str) -> Option<String>;
+
+    async fn power_level(&self, room_id: &str, user_id: &str) -> i64;
+
+    async fn member_count(&self, room_id: &str) -> Option<u64>;
+
+    async fn join_room(&self, room_id: &str) -> Result<(), Error>;
+
+    async fn leave_room(&self, room_id: &str) -> Result<(), Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reply_to() -> Result<(), Error> {
+        let mut message = IncomingMessage::text(
+            "!room:example.org",
+            "@alice:example.org",
+            "$trigger",
+            "I'm hungry",
+        );
+        assert_eq!(message.reply_to(false), None);
+        assert_eq!(
+            message.reply_to(true),
+            Some(ReplyTo {
+                event_id: String::from("$trigger"),
+                sender: String::from("@alice:example.org"),
+                body: String::from("I'm hungry"),
+                quote: true,
+                thread_root: None,
+            })
+        );
+        message.thread_root = Some(String::from("$root"));
+        assert_eq!(
+            message.reply_to(false),
+            Some(ReplyTo {
+                event_id: String::from("$trigger"),
+                sender: String::from("@alice:example.org"),
+                body: String::from("I'm hungry"),
+                quote: false,
+                thread_root: Some(String::from("$root")),
+            })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+/// A built-in trigger and set of replies for one language
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalePack {
+    pub code: &'static str,
+    pub regex: &'static str,
+    pub templates: &'static [&'static str],
+    pub love_templates: &'static [&'static str],
+}
+
+pub const LOCALE_PACKS: &[LocalePack] = &[
+    LocalePack {
+        code: "en",
+        regex: r"\b(?P<im>(?:i|l)(?:(?:'|`|‛|‘|’|′|‵)?m| am))(?:\s+)(?P<dad_text>[^\.!?]+)",
+        templates: &["Hi {dad_text}! I'm Dad!"],
+        love_templates: &["Hi {dad_text}! I'm Dad and I love you!"],
+    },
+    LocalePack {
+        code: "de",
+        regex: r"\b(?P<im>ich bin)(?:\s+)(?P<dad_text>[^\.!?]+)",
+        templates: &["Hallo {dad_text}, ich bin Papa!"],
+        love_templates: &["Hallo {dad_text}, ich bin Papa und ich hab dich lieb!"],
+    },
+    LocalePack {
+        code: "es",
+        regex: r"\b(?P<im>(?:yo\s+)?(?:soy|estoy))(?:\s+)(?P<dad_text>[^\.!?¡¿]+)",
+        templates: &["¡Hola {dad_text}, soy papá!"],
+        love_templates: &["¡Hola {dad_text}, soy papá y te quiero!"],
+    },
+    LocalePack {
+        code: "fr",
+        regex: r"\b(?P<im>je suis)(?:\s+)(?P<dad_text>[^\.!?]+)",
+        templates: &["Salut {dad_text}, moi c'est Papa !"],
+        love_templates: &["Salut {dad_text}, moi c'est Papa et je t'aime !"],
+    },
+];
+
+pub fn get_locale_pack(code: &str) -> Option<&'static LocalePack> {
+    LOCALE_PACKS
+        .iter()
+        .find(|pack| pack.code.eq_ignore_ascii_case(code))
+}
@@ -0,0 +1,184 @@
+use crate::commands::utils::EnabledChance;
+use crate::config::Config;
+use crate::errors::Error;
+use getset::Getters;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use tracing::*;
+
+pub use self::locales::{get_locale_pack, LocalePack, LOCALE_PACKS};
+mod locales;
+
+/// A pattern that gets a dadded reply, along with the replies it can get
+#[derive(Debug, Clone, Getters)]
+pub struct Trigger {
+    #[getset(get = "pub")]
+    regex: Regex,
+    #[getset(get = "pub")]
+    templates: Vec<String>,
+    #[getset(get = "pub")]
+    love_templates: Vec<String>,
+    #[getset(get = "pub")]
+    chance: Option<EnabledChance>,
+    #[getset(get = "pub")]
+    locale: Option<String>,
+}
+
+impl Trigger {
+    pub fn new(
+        pattern: &str,
+        templates: Vec<String>,
+        love_templates: Vec<String>,
+        chance: Option<i64>,
+    ) -> Result<Self, Error> {
+        let regex = RegexBuilder::new(pattern).case_insensitive(true).build()?;
+        Ok(Self {
+            regex,
+            templates,
+            love_templates,
+            chance: chance.map(|c| EnabledChance::dadded(Some(c))),
+            locale: None,
+        })
+    }
+
+    pub fn from_locale_pack(pack: &LocalePack) -> Result<Self, Error> {
+        let to_strings = |templates: &[&str]| templates.iter().map(|t| t.to_string()).collect();
+        let mut trigger = Self::new(
+            pack.regex,
+            to_strings(pack.templates),
+            to_strings(pack.love_templates),
+            None,
+        )?;
+        trigger.locale = Some(pack.code.to_string());
+        Ok(trigger)
+    }
+
+    /// Triggers without a locale apply everywhere, locale packs only where the room allows them
+    pub fn is_allowed_in(&self, room_locale: Option<&str>) -> bool {
+        match (&self.locale, room_locale) {
+            (Some(locale), Some(room_locale)) => locale.eq_ignore_ascii_case(room_locale),
+            _ => true,
+        }
+    }
+
+    pub fn get_templates(&self, should_love: bool) -> &[String] {
+        if should_love {
+            &self.love_templates
+        } else {
+            &self.templates
+        }
+    }
+}
+
+/// All configured triggers, matched against a message in a single pass
+#[derive(Debug, Clone)]
+pub struct Triggers {
+    set: RegexSet,
+    triggers: Vec<Trigger>,
+}
+
+impl Triggers {
+    pub fn new(triggers: Vec<Trigger>) -> Result<Self, Error> {
+        let set = RegexSetBuilder::new(triggers.iter().map(|t| t.regex().as_str()))
+            .case_insensitive(true)
+            .build()?;
+        Ok(Self { set, triggers })
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let mut triggers = Vec::new();
+        for trigger in config.triggers.iter().flatten() {
+            triggers.push(Trigger::new(
+                &trigger.regex,
+                trigger.get_templates(config),
+                trigger.get_love_templates(config),
+                trigger.chance,
+            )?);
+        }
+        triggers.push(Trigger::new(
+            &config.dadded_regex,
+            config.get_templates(),
+            config.get_love_templates(),
+            None,
+        )?);
+        for code in config.locales.iter().flatten() {
+            match get_locale_pack(code) {
+                Some(pack) => triggers.push(Trigger::from_locale_pack(pack)?),
+                None => return Err(Error::UnknownLocale(code.to_string())),
+            }
+        }
+        info!("Compiled {} dadded triggers", triggers.len());
+        Self::new(triggers)
+    }
+
+    /// The first trigger, in config order, that matches the message and is allowed in the room
+    pub fn find(&self, msg: &str, room_locale: Option<&str>) -> Option<&Trigger> {
+        self.set
+            .matches(msg)
+            .iter()
+            .map(|idx| &self.triggers[idx])
+            .find(|trigger| trigger.is_allowed_in(room_locale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(pattern: &str, template: &str) -> Trigger {
+        Trigger::new(pattern, vec![template.to_string()], vec![], None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_find_first_matching_trigger() -> Result<(), Error> {
+        let triggers = Triggers::new(vec![
+            trigger(r"\bI feel (?P<dad_text>\w+)", "feel"),
+            trigger(r"\bI'm gonna (?P<dad_text>\w+)", "gonna"),
+            trigger(r"\bI'm (?P<dad_text>\w+)", "im"),
+        ])?;
+
+        let templates = |msg: &str| triggers.find(msg, None).map(|t| t.templates()[0].clone());
+        assert_eq!(templates("i feel sleepy"), Some(String::from("feel")));
+        assert_eq!(templates("I'm gonna nap"), Some(String::from("gonna")));
+        assert_eq!(templates("I'm hungry"), Some(String::from("im")));
+        assert_eq!(templates("Hello there"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locale_packs_compile() -> Result<(), Error> {
+        for pack in LOCALE_PACKS {
+            let trigger = Trigger::from_locale_pack(pack)?;
+            assert_eq!(trigger.locale(), &Some(pack.code.to_string()));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_locale_pack() -> Result<(), Error> {
+        let triggers = Triggers::new(vec![
+            trigger(r"I feel (?P<dad_text>\w+)", "feel"),
+            Trigger::from_locale_pack(get_locale_pack("en").unwrap())?,
+            Trigger::from_locale_pack(get_locale_pack("de").unwrap())?,
+            Trigger::from_locale_pack(get_locale_pack("es").unwrap())?,
+        ])?;
+
+        let locale = |msg: &str, room: Option<&str>| {
+            triggers
+                .find(msg, room)
+                .map(|t| t.locale().clone().unwrap_or_default())
+        };
+        assert_eq!(locale("Ich bin müde", None), Some(String::from("de")));
+        assert_eq!(locale("Estoy cansado", None), Some(String::from("es")));
+        assert_eq!(locale("I'm tired", Some("DE")), None);
+        assert_eq!(locale("Ich bin müde", Some("de")), Some(String::from("de")));
+        assert_eq!(locale("I feel tired", Some("de")), Some(String::new()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_trigger() -> Result<(), Error> {
+        let trigger = Trigger::new(r"(?P<dad_text>", vec![], vec![], None);
+        assert!(trigger.is_err());
+        Ok(())
+    }
+}
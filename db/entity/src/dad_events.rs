@@ -0,0 +1,22 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "dad_events")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    pub created_at: DateTimeLocal,
+    pub room_id: String,
+    pub sender: String,
+    pub trigger_event_id: String,
+    pub reply_event_id: String,
+    pub dad_text: String,
+    pub loved: bool,
+    // Template the reply was made from, edits of the trigger reuse it. Empty for older replies
+    pub template: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,12 +1,18 @@
 use sea_orm::entity::prelude::*;
 
+/// `room_id` of the dads counted before they were kept per room, which room they were in is lost
+pub const UNKNOWN_ROOM_ID: &str = "";
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "got_dadded")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: u32,
     pub epoch_id: u32,
+    pub room_id: String,
     pub count: u32,
+    // Reactions sent, `count` is replies and a dad in both modes adds to each
+    pub reaction_count: u32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -10,8 +10,10 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
-    #[sea_orm(has_one = "super::dadded::Entity")]
+    #[sea_orm(has_many = "super::dadded::Entity")]
     Dadded,
+    #[sea_orm(has_many = "super::victims::Entity")]
+    Victims,
 }
 
 impl Related<super::dadded::Entity> for Entity {
@@ -20,4 +22,10 @@ impl Related<super::dadded::Entity> for Entity {
     }
 }
 
+impl Related<super::victims::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Victims.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
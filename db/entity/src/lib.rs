@@ -1,7 +1,15 @@
+pub mod dad_events;
 pub mod dadded;
 pub mod epochs;
+pub mod opt_outs;
+pub mod room_settings;
+pub mod victims;
 
+pub use dad_events::Entity as DadEvent;
 pub use dadded::Entity as Dadded;
 pub use epochs::Entity as Epoch;
+pub use opt_outs::Entity as OptOut;
+pub use room_settings::Entity as RoomSettings;
+pub use victims::Entity as Victim;
 
 pub use sea_orm;
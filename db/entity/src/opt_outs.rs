@@ -0,0 +1,16 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "opt_outs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    pub mxid: String,
+    // `None` opts out of every room
+    pub room_id: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "room_settings")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    #[sea_orm(unique)]
+    pub room_id: String,
+    pub enabled: bool,
+    // Unset values fall back to the bot config
+    pub dadded_chance: Option<i64>,
+    pub love_me_chance: Option<i64>,
+    pub locale: Option<String>,
+    pub cooldown: Option<i64>,
+    // One of reply, react or both
+    pub dad_mode: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -2,6 +2,13 @@ pub use sea_schema::migration::*;
 
 mod m20220311_000001_create_epoch_table;
 mod m20220311_000002_create_get_dadded_table;
+mod m20261018_000003_add_room_to_got_dadded_table;
+mod m20261018_000004_create_victims_table;
+mod m20261018_000005_create_dad_events_table;
+mod m20261018_000006_create_room_settings_table;
+mod m20261018_000007_create_opt_outs_table;
+mod m20261018_000008_add_reaction_dads;
+mod m20261018_000009_add_template_to_dad_events;
 mod util;
 
 pub struct Migrator;
@@ -12,6 +19,13 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220311_000001_create_epoch_table::Migration),
             Box::new(m20220311_000002_create_get_dadded_table::Migration),
+            Box::new(m20261018_000003_add_room_to_got_dadded_table::Migration),
+            Box::new(m20261018_000004_create_victims_table::Migration),
+            Box::new(m20261018_000005_create_dad_events_table::Migration),
+            Box::new(m20261018_000006_create_room_settings_table::Migration),
+            Box::new(m20261018_000007_create_opt_outs_table::Migration),
+            Box::new(m20261018_000008_add_reaction_dads::Migration),
+            Box::new(m20261018_000009_add_template_to_dad_events::Migration),
         ]
     }
 }
@@ -0,0 +1,47 @@
+use crate::util::has_column;
+use sea_schema::migration::{sea_query::*, *};
+
+use entity::{dadded, Dadded};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000003_add_room_to_got_dadded_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if has_column(manager, Dadded, dadded::Column::RoomId).await? {
+            return Ok(());
+        }
+        // Existing counts can't be put in a room anymore, they only show up in totals across
+        // all rooms and next to a room's own counts
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Dadded)
+                    .add_column(
+                        ColumnDef::new(dadded::Column::RoomId)
+                            .string()
+                            .not_null()
+                            .default(dadded::UNKNOWN_ROOM_ID),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Dadded)
+                    .drop_column(dadded::Column::RoomId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,30 @@
+use crate::util::create_table_statement;
+use sea_schema::migration::{sea_query::*, *};
+
+use entity::DadEvent;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000005_create_dad_events_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(create_table_statement(
+                manager.get_database_backend(),
+                DadEvent,
+            ))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DadEvent).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,63 @@
+use crate::util::has_column;
+use sea_schema::migration::{sea_query::*, *};
+
+use entity::{dadded, room_settings, Dadded, RoomSettings};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000008_add_reaction_dads"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !has_column(manager, Dadded, dadded::Column::ReactionCount).await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Dadded)
+                        .add_column(
+                            ColumnDef::new(dadded::Column::ReactionCount)
+                                .integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        if !has_column(manager, RoomSettings, room_settings::Column::DadMode).await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(RoomSettings)
+                        .add_column(ColumnDef::new(room_settings::Column::DadMode).string())
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RoomSettings)
+                    .drop_column(room_settings::Column::DadMode)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Dadded)
+                    .drop_column(dadded::Column::ReactionCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
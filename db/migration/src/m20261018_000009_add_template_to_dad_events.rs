@@ -0,0 +1,46 @@
+use crate::util::has_column;
+use sea_schema::migration::{sea_query::*, *};
+
+use entity::{dad_events, DadEvent};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000009_add_template_to_dad_events"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if has_column(manager, DadEvent, dad_events::Column::Template).await? {
+            return Ok(());
+        }
+        // Replies sent before this don't know their template, edits pick a new one for them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DadEvent)
+                    .add_column(
+                        ColumnDef::new(dad_events::Column::Template)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DadEvent)
+                    .drop_column(dad_events::Column::Template)
+                    .to_owned(),
+            )
+            .await
+    }
+}
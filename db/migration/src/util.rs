@@ -1,5 +1,11 @@
-use entity::sea_orm::{sea_query::table::TableCreateStatement, DbBackend, EntityTrait, Schema};
+use entity::sea_orm::{
+    sea_query::{table::TableCreateStatement, Iden},
+    ConnectionTrait, DbBackend, DbErr, EntityTrait, Schema, Statement,
+};
+use sea_schema::migration::SchemaManager;
 
+// Tables are created from their entity, so a fresh database already has every column. Migrations
+// that add a column to an existing table have to check for it first, see `has_column`.
 pub fn create_table_statement<E>(db: DbBackend, entity: E) -> TableCreateStatement
 where
     E: EntityTrait,
@@ -9,3 +15,38 @@ where
         .if_not_exists()
         .to_owned()
 }
+
+/// Whether `table` already has `column`, databases created after the column was added to the
+/// entity got it with the table
+pub async fn has_column<T, C>(
+    manager: &SchemaManager<'_>,
+    table: T,
+    column: C,
+) -> Result<bool, DbErr>
+where
+    T: Iden,
+    C: Iden,
+{
+    let backend = manager.get_database_backend();
+    let sql = match backend {
+        DbBackend::Sqlite => "SELECT COUNT(*) AS count FROM pragma_table_info(?) WHERE name = ?",
+        DbBackend::Postgres => {
+            "SELECT COUNT(*) AS count FROM information_schema.columns \
+             WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2"
+        }
+        DbBackend::MySql => {
+            "SELECT COUNT(*) AS count FROM information_schema.columns \
+             WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ?"
+        }
+    };
+    let statement = Statement::from_sql_and_values(
+        backend,
+        sql,
+        vec![table.to_string().into(), column.to_string().into()],
+    );
+    let count = match manager.get_connection().query_one(statement).await? {
+        Some(row) => row.try_get::<i64>("", "count")?,
+        None => 0,
+    };
+    Ok(count > 0)
+}
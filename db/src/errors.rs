@@ -13,4 +13,6 @@ pub enum Error {
     EpochNotFound { id: u32 },
     #[error("Dadded with id [{id}] doesn't exist")]
     DaddedNotFound { id: u32 },
+    #[error("Victim with id [{id}] doesn't exist")]
+    VictimNotFound { id: u32 },
 }
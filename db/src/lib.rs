@@ -1,10 +1,18 @@
 mod errors;
 pub mod utils;
+pub use entity::dad_events as DadEvent;
+pub use entity::dad_events;
 pub use entity::dadded as Dadded;
 pub use entity::dadded;
 pub use entity::epochs as Epoch;
 pub use entity::epochs;
+pub use entity::opt_outs as OptOut;
+pub use entity::opt_outs;
+pub use entity::room_settings as RoomSettings;
+pub use entity::room_settings;
 pub use entity::sea_orm;
+pub use entity::victims as Victim;
+pub use entity::victims;
 pub use migration;
 
 pub use crate::errors::Error;
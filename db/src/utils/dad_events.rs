@@ -0,0 +1,171 @@
+use crate::errors::Error;
+use crate::sea_orm::*;
+use crate::DadEvent;
+use chrono::{DateTime, Local};
+use tracing::*;
+
+/// A dad reply that was sent and should be appended to the event log
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewDadEvent {
+    pub room_id: String,
+    pub sender: String,
+    pub trigger_event_id: String,
+    pub reply_event_id: String,
+    pub dad_text: String,
+    pub loved: bool,
+    pub template: String,
+}
+
+pub async fn create_dad_event(
+    db: &DbConn,
+    created_at: &DateTime<Local>,
+    event: NewDadEvent,
+) -> Result<DadEvent::Model, Error> {
+    let event_model = DadEvent::ActiveModel {
+        created_at: Set(created_at.to_owned()),
+        room_id: Set(event.room_id),
+        sender: Set(event.sender),
+        trigger_event_id: Set(event.trigger_event_id),
+        reply_event_id: Set(event.reply_event_id),
+        dad_text: Set(event.dad_text),
+        loved: Set(event.loved),
+        template: Set(event.template),
+        ..Default::default()
+    };
+    let dad_event = event_model.insert(db).await?;
+    info!(
+        "Created DadEvent {{ id: {}, room: {}, trigger: {}, reply: {} }}",
+        dad_event.id, dad_event.room_id, dad_event.trigger_event_id, dad_event.reply_event_id
+    );
+    Ok(dad_event)
+}
+
+pub async fn get_dad_events_from_room(
+    db: &DbConn,
+    room_id: &str,
+) -> Result<Vec<DadEvent::Model>, Error> {
+    let events = DadEvent::Entity::find()
+        .filter(DadEvent::Column::RoomId.eq(room_id))
+        .order_by_asc(DadEvent::Column::Id)
+        .all(db)
+        .await?;
+    Ok(events)
+}
+
+pub async fn get_dad_event_by_trigger(
+    db: &DbConn,
+    room_id: &str,
+    trigger_event_id: &str,
+) -> Result<Option<DadEvent::Model>, Error> {
+    let event = DadEvent::Entity::find()
+        .filter(DadEvent::Column::RoomId.eq(room_id))
+        .filter(DadEvent::Column::TriggerEventId.eq(trigger_event_id))
+        .one(db)
+        .await?;
+    Ok(event)
+}
+
+/// Updates what a reply dadded after its trigger was edited, and the template
+/// it was made from when the old one wasn't known
+pub async fn set_dad_text(
+    db: &DbConn,
+    event: DadEvent::Model,
+    dad_text: &str,
+    template: &str,
+) -> Result<DadEvent::Model, Error> {
+    let mut active_event: DadEvent::ActiveModel = event.into();
+    active_event.dad_text = Set(dad_text.to_string());
+    active_event.template = Set(template.to_string());
+    let new_event = active_event.update(db).await?;
+    info!(
+        "Updated DadEvent {{ id: {}, room: {} }} with dad_text {}",
+        new_event.id, new_event.room_id, new_event.dad_text
+    );
+    Ok(new_event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::integration_utils;
+    use chrono::{TimeZone, Utc};
+
+    fn new_event(room_id: &str, trigger_event_id: &str, loved: bool) -> NewDadEvent {
+        NewDadEvent {
+            room_id: room_id.to_string(),
+            sender: String::from("@alice:example.org"),
+            trigger_event_id: trigger_event_id.to_string(),
+            reply_event_id: format!("{}-reply", trigger_event_id),
+            dad_text: String::from("hungry"),
+            loved,
+            template: String::from("Hi {dad_text}!"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_integration_create_dad_event() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let created_at: DateTime<Local> = Utc.ymd(2022, 3, 16).and_hms_milli(12, 1, 2, 0).into();
+        let room_id = "!room:example.org";
+
+        let dad_event =
+            create_dad_event(&db, &created_at, new_event(room_id, "$trigger", true)).await?;
+
+        assert_eq!(dad_event.created_at, created_at);
+        assert_eq!(dad_event.room_id, room_id);
+        assert_eq!(dad_event.trigger_event_id, "$trigger");
+        assert_eq!(dad_event.reply_event_id, "$trigger-reply");
+        assert_eq!(dad_event.dad_text, "hungry");
+        assert!(dad_event.loved);
+        assert_eq!(dad_event.template, "Hi {dad_text}!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_get_dad_events_from_room() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let created_at: DateTime<Local> = Utc.ymd(2022, 3, 16).and_hms_milli(12, 1, 2, 0).into();
+        let room_id = "!room:example.org";
+
+        create_dad_event(&db, &created_at, new_event(room_id, "$first", false)).await?;
+        create_dad_event(
+            &db,
+            &created_at,
+            new_event("!other:example.org", "$x", false),
+        )
+        .await?;
+        create_dad_event(&db, &created_at, new_event(room_id, "$second", true)).await?;
+
+        let events = get_dad_events_from_room(&db, room_id).await?;
+        let triggers = events
+            .iter()
+            .map(|e| e.trigger_event_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(triggers, vec!["$first", "$second"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_edit_dad_event() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let created_at: DateTime<Local> = Utc.ymd(2022, 3, 16).and_hms_milli(12, 1, 2, 0).into();
+        let room_id = "!room:example.org";
+
+        create_dad_event(&db, &created_at, new_event(room_id, "$trigger", false)).await?;
+        assert_eq!(
+            get_dad_event_by_trigger(&db, "!other:example.org", "$trigger").await?,
+            None
+        );
+        let event = get_dad_event_by_trigger(&db, room_id, "$trigger")
+            .await?
+            .unwrap();
+        let edited = set_dad_text(&db, event, "thirsty", "Hi {dad_text}!").await?;
+        assert_eq!(edited.dad_text, "thirsty");
+        assert_eq!(edited.template, "Hi {dad_text}!");
+        assert_eq!(
+            get_dad_event_by_trigger(&db, room_id, "$trigger").await?,
+            Some(edited)
+        );
+        Ok(())
+    }
+}
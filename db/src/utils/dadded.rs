@@ -1,25 +1,34 @@
 use crate::errors::Error;
 use crate::sea_orm::*;
+use crate::utils::epochs;
 use crate::{Dadded, Epoch};
 use tracing::*;
 
 pub async fn get_or_create_dad_from_epoch(
     db: &DbConn,
     epoch_id: u32,
+    room_id: &str,
 ) -> Result<Dadded::Model, Error> {
     if let Some(epoch) = Epoch::Entity::find_by_id(epoch_id).one(db).await? {
-        if let Some(dad) = epoch.find_related(Dadded::Entity).one(db).await? {
+        if let Some(dad) = epoch
+            .find_related(Dadded::Entity)
+            .filter(Dadded::Column::RoomId.eq(room_id))
+            .one(db)
+            .await?
+        {
             Ok(dad)
         } else {
             let dadded_model = Dadded::ActiveModel {
                 epoch_id: Set(epoch_id),
+                room_id: Set(room_id.to_owned()),
                 count: Set(0),
+                reaction_count: Set(0),
                 ..Default::default()
             };
             let dad = dadded_model.insert(db).await?;
             info!(
-                "Created Dadded {{ id: {}, epoch: {} }}",
-                dad.id, dad.epoch_id
+                "Created Dadded {{ id: {}, epoch: {}, room: {} }}",
+                dad.id, dad.epoch_id, dad.room_id
             );
             Ok(dad)
         }
@@ -28,6 +37,64 @@ pub async fn get_or_create_dad_from_epoch(
     }
 }
 
+/// The dads of a room in an epoch without creating them when there aren't any yet
+pub async fn find_dad_from_epoch(
+    db: &DbConn,
+    epoch_id: u32,
+    room_id: &str,
+) -> Result<Option<Dadded::Model>, Error> {
+    let dad = Dadded::Entity::find()
+        .filter(Dadded::Column::EpochId.eq(epoch_id))
+        .filter(Dadded::Column::RoomId.eq(room_id))
+        .one(db)
+        .await?;
+    Ok(dad)
+}
+
+pub async fn get_dads_from_epoch(db: &DbConn, epoch_id: u32) -> Result<Vec<Dadded::Model>, Error> {
+    if let Some(epoch) = Epoch::Entity::find_by_id(epoch_id).one(db).await? {
+        let dads = epoch.find_related(Dadded::Entity).all(db).await?;
+        Ok(dads)
+    } else {
+        Err(Error::EpochNotFound { id: epoch_id })
+    }
+}
+
+pub async fn get_total_dads_from_epoch(db: &DbConn, epoch_id: u32) -> Result<u32, Error> {
+    let dads = get_dads_from_epoch(db, epoch_id).await?;
+    Ok(dads.iter().map(|dad| dad.count).sum())
+}
+
+/// Reactions sent in an epoch, whether or not a reply went with them
+pub async fn get_total_reactions_from_epoch(db: &DbConn, epoch_id: u32) -> Result<u32, Error> {
+    let dads = get_dads_from_epoch(db, epoch_id).await?;
+    Ok(dads.iter().map(|dad| dad.reaction_count).sum())
+}
+
+/// Dad and reaction counts of a room for the latest epochs, newest epoch first
+pub async fn get_room_history(
+    db: &DbConn,
+    room_id: &str,
+    epoch_count: u64,
+) -> Result<Vec<(Epoch::Model, u32, u32)>, Error> {
+    let latest_epochs = epochs::get_latest_epochs(db, epoch_count).await?;
+    let dads = Dadded::Entity::find()
+        .filter(Dadded::Column::RoomId.eq(room_id))
+        .filter(Dadded::Column::EpochId.is_in(latest_epochs.iter().map(|epoch| epoch.id)))
+        .all(db)
+        .await?;
+    let history = latest_epochs
+        .into_iter()
+        .map(|epoch| {
+            let epoch_dads = dads.iter().filter(|dad| dad.epoch_id == epoch.id);
+            let count = epoch_dads.clone().map(|dad| dad.count).sum();
+            let reactions = epoch_dads.map(|dad| dad.reaction_count).sum();
+            (epoch, count, reactions)
+        })
+        .collect();
+    Ok(history)
+}
+
 pub async fn increament_dadded(db: &DbConn, dadded_id: u32) -> Result<Dadded::Model, Error> {
     if let Some(dadded) = Dadded::Entity::find_by_id(dadded_id).one(db).await? {
         let current_count = dadded.count;
@@ -44,6 +111,25 @@ pub async fn increament_dadded(db: &DbConn, dadded_id: u32) -> Result<Dadded::Mo
     }
 }
 
+pub async fn increament_reaction_dadded(
+    db: &DbConn,
+    dadded_id: u32,
+) -> Result<Dadded::Model, Error> {
+    if let Some(dadded) = Dadded::Entity::find_by_id(dadded_id).one(db).await? {
+        let current_count = dadded.reaction_count;
+        let mut active_dadded: Dadded::ActiveModel = dadded.into();
+        active_dadded.reaction_count = Set(current_count + 1);
+        let new_dadded = active_dadded.update(db).await?;
+        info!(
+            "Updated Dadded {{ id: {} }} reaction count was: {}, now: {}",
+            new_dadded.id, current_count, new_dadded.reaction_count
+        );
+        Ok(new_dadded)
+    } else {
+        Err(Error::DaddedNotFound { id: dadded_id })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,6 +144,7 @@ mod tests {
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
         let epoch_id = 1;
+        let room_id = "!room:example.org";
         let dadded_id = 1;
         let count = 0;
         let db = MockDatabase::new(DatabaseBackend::Sqlite)
@@ -70,7 +157,9 @@ mod tests {
                 vec![Dadded::Model {
                     id: dadded_id,
                     epoch_id: epoch_id,
+                    room_id: room_id.to_owned(),
                     count: 0,
+                    reaction_count: 0,
                 }],
             ])
             .append_exec_results(vec![MockExecResult {
@@ -78,7 +167,7 @@ mod tests {
                 rows_affected: 1,
             }])
             .into_connection();
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await?;
         assert_eq!(dadded_id, res_dad.id);
         assert_eq!(epoch_id, res_dad.epoch_id);
         assert_eq!(count, res_dad.count);
@@ -91,6 +180,7 @@ mod tests {
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
         let epoch_id = 1;
+        let room_id = "!room:example.org";
         let dadded_id = 1;
         let count = 32;
         let db = MockDatabase::new(DatabaseBackend::Sqlite)
@@ -101,10 +191,12 @@ mod tests {
             .append_query_results(vec![vec![Dadded::Model {
                 id: dadded_id,
                 epoch_id: epoch_id,
+                room_id: room_id.to_owned(),
                 count: count,
+                reaction_count: 0,
             }]])
             .into_connection();
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await?;
         assert_eq!(dadded_id, res_dad.id);
         assert_eq!(epoch_id, res_dad.epoch_id);
         assert_eq!(count, res_dad.count);
@@ -114,11 +206,12 @@ mod tests {
     #[tokio::test]
     async fn test_get_or_create_dad_epoch_doesnt_exist() -> Result<(), Error> {
         let epoch_id = 1;
+        let room_id = "!room:example.org";
         let epoch_vec: Vec<Epoch::Model> = vec![];
         let db = MockDatabase::new(DatabaseBackend::Sqlite)
             .append_query_results(vec![epoch_vec])
             .into_connection();
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await;
         assert_eq!(Error::EpochNotFound { id: 1 }, res_dad.unwrap_err());
         Ok(())
     }
@@ -129,6 +222,7 @@ mod tests {
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
         let epoch_id = 1;
+        let room_id = "!room:example.org";
         let dadded_id = 1;
         let count = 0;
         let db = MockDatabase::new(DatabaseBackend::Sqlite)
@@ -140,17 +234,23 @@ mod tests {
                 vec![Dadded::Model {
                     id: dadded_id,
                     epoch_id: epoch_id,
+                    room_id: room_id.to_owned(),
                     count: count,
+                    reaction_count: 0,
                 }],
                 vec![Dadded::Model {
                     id: dadded_id,
                     epoch_id: epoch_id,
+                    room_id: room_id.to_owned(),
                     count: count,
+                    reaction_count: 0,
                 }],
                 vec![Dadded::Model {
                     id: dadded_id,
                     epoch_id: epoch_id,
+                    room_id: room_id.to_owned(),
                     count: count + 1,
+                    reaction_count: 0,
                 }],
             ])
             .append_exec_results(vec![MockExecResult {
@@ -158,7 +258,7 @@ mod tests {
                 rows_affected: 1,
             }])
             .into_connection();
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await?;
         let new_dad = increament_dadded(&db, res_dad.id).await?;
         assert_eq!(new_dad.id, res_dad.id);
         assert_eq!(epoch_id, new_dad.epoch_id);
@@ -169,6 +269,7 @@ mod tests {
     #[tokio::test]
     async fn test_integration_get_or_create_dad_does_not_exist() -> Result<(), Error> {
         let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
         let d1 = NaiveDate::from_ymd(2022, 3, 16);
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
@@ -183,7 +284,7 @@ mod tests {
         let dadded_id = 1;
         let count = 0;
 
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await?;
         assert_eq!(dadded_id, res_dad.id);
         assert_eq!(epoch_id, res_dad.epoch_id);
         assert_eq!(count, res_dad.count);
@@ -193,6 +294,7 @@ mod tests {
     #[tokio::test]
     async fn test_integration_get_or_create_dad_does_exist() -> Result<(), Error> {
         let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
         let d1 = NaiveDate::from_ymd(2022, 3, 16);
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
@@ -206,12 +308,14 @@ mod tests {
 
         let dad_am = Dadded::ActiveModel {
             epoch_id: Set(epoch.id),
+            room_id: Set(room_id.to_owned()),
             count: Set(count),
+            reaction_count: Set(0),
             ..Default::default()
         };
         let dad = dad_am.insert(&db).await?;
 
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch.id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
         assert_eq!(dad.id, res_dad.id);
         assert_eq!(dad.epoch_id, res_dad.epoch_id);
         assert_eq!(dad.count, res_dad.count);
@@ -222,7 +326,8 @@ mod tests {
     async fn test_integration_get_or_create_dad_epoch_doesnt_exist() -> Result<(), Error> {
         let db = integration_utils::create_inmemory_db().await?;
         let epoch_id = 1;
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id).await;
+        let room_id = "!room:example.org";
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch_id, room_id).await;
         assert_eq!(Error::EpochNotFound { id: 1 }, res_dad.unwrap_err());
         Ok(())
     }
@@ -230,6 +335,7 @@ mod tests {
     #[tokio::test]
     async fn test_integration_increament_dadded_count() -> Result<(), Error> {
         let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
         let d1 = NaiveDate::from_ymd(2022, 3, 16);
         let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
         let dt1 = NaiveDateTime::new(d1, t1);
@@ -244,12 +350,14 @@ mod tests {
 
         let dad_am = Dadded::ActiveModel {
             epoch_id: Set(epoch.id),
+            room_id: Set(room_id.to_owned()),
             count: Set(count),
+            reaction_count: Set(0),
             ..Default::default()
         };
         let dad = dad_am.insert(&db).await?;
 
-        let res_dad = get_or_create_dad_from_epoch(&db, epoch.id).await?;
+        let res_dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
         let new_dad = increament_dadded(&db, res_dad.id).await?;
         assert_eq!(res_dad.id, dad.id);
         assert_eq!(new_dad.id, res_dad.id);
@@ -257,4 +365,123 @@ mod tests {
         assert_eq!(new_dad.count, res_dad.count + 1);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_integration_dads_are_per_room() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let other_room_id = "!other:example.org";
+        let d1 = NaiveDate::from_ymd(2022, 3, 16);
+        let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let dt1 = NaiveDateTime::new(d1, t1);
+
+        let epoch_am = Epoch::ActiveModel {
+            epoch: Set(Local.from_utc_datetime(&dt1)),
+            ..Default::default()
+        };
+        let epoch = epoch_am.insert(&db).await?;
+
+        let room_dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
+        let other_room_dad = get_or_create_dad_from_epoch(&db, epoch.id, other_room_id).await?;
+        increament_dadded(&db, room_dad.id).await?;
+        increament_dadded(&db, room_dad.id).await?;
+        increament_dadded(&db, other_room_dad.id).await?;
+
+        let room_dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
+        let other_room_dad = get_or_create_dad_from_epoch(&db, epoch.id, other_room_id).await?;
+        let total = get_total_dads_from_epoch(&db, epoch.id).await?;
+        assert_ne!(room_dad.id, other_room_dad.id);
+        assert_eq!(room_dad.count, 2);
+        assert_eq!(other_room_dad.count, 1);
+        assert_eq!(total, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_find_dad_from_epoch() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let d1 = NaiveDate::from_ymd(2022, 3, 16);
+        let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let dt1 = NaiveDateTime::new(d1, t1);
+
+        let epoch_am = Epoch::ActiveModel {
+            epoch: Set(Local.from_utc_datetime(&dt1)),
+            ..Default::default()
+        };
+        let epoch = epoch_am.insert(&db).await?;
+
+        assert_eq!(find_dad_from_epoch(&db, epoch.id, room_id).await?, None);
+        let dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
+        assert_eq!(
+            find_dad_from_epoch(&db, epoch.id, room_id).await?,
+            Some(dad)
+        );
+        assert_eq!(
+            find_dad_from_epoch(&db, epoch.id, Dadded::UNKNOWN_ROOM_ID).await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_get_room_history() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let mut epochs = Vec::new();
+        for day in 14..17 {
+            let dt = NaiveDateTime::new(NaiveDate::from_ymd(2022, 3, day), t1);
+            let epoch_am = Epoch::ActiveModel {
+                epoch: Set(Local.from_utc_datetime(&dt)),
+                ..Default::default()
+            };
+            epochs.push(epoch_am.insert(&db).await?);
+        }
+
+        let old_dad = get_or_create_dad_from_epoch(&db, epochs[1].id, room_id).await?;
+        increament_dadded(&db, old_dad.id).await?;
+        increament_reaction_dadded(&db, old_dad.id).await?;
+        let other_dad =
+            get_or_create_dad_from_epoch(&db, epochs[2].id, "!other:example.org").await?;
+        increament_dadded(&db, other_dad.id).await?;
+
+        let history = get_room_history(&db, room_id, 2).await?;
+        let history = history
+            .iter()
+            .map(|(epoch, count, reactions)| (epoch.id, *count, *reactions))
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![(epochs[2].id, 0, 0), (epochs[1].id, 1, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_reactions_are_counted_separately() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let d1 = NaiveDate::from_ymd(2022, 3, 16);
+        let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let dt1 = NaiveDateTime::new(d1, t1);
+
+        let epoch_am = Epoch::ActiveModel {
+            epoch: Set(Local.from_utc_datetime(&dt1)),
+            ..Default::default()
+        };
+        let epoch = epoch_am.insert(&db).await?;
+
+        let dad = get_or_create_dad_from_epoch(&db, epoch.id, room_id).await?;
+        increament_dadded(&db, dad.id).await?;
+        increament_reaction_dadded(&db, dad.id).await?;
+        let dad = increament_reaction_dadded(&db, dad.id).await?;
+
+        assert_eq!(dad.count, 1);
+        assert_eq!(dad.reaction_count, 2);
+        assert_eq!(get_total_dads_from_epoch(&db, epoch.id).await?, 1);
+        assert_eq!(get_total_reactions_from_epoch(&db, epoch.id).await?, 2);
+        assert_eq!(
+            increament_reaction_dadded(&db, 42).await.unwrap_err(),
+            Error::DaddedNotFound { id: 42 }
+        );
+        Ok(())
+    }
 }
@@ -89,6 +89,15 @@ pub async fn get_next_epoch_bound(
     }
 }
 
+pub async fn get_latest_epochs(db: &DbConn, count: u64) -> Result<Vec<Epoch::Model>, Error> {
+    let epochs = Epoch::Entity::find()
+        .order_by_desc(epochs::Column::Epoch)
+        .limit(count)
+        .all(db)
+        .await?;
+    Ok(epochs)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -512,4 +521,27 @@ mod tests {
         assert_eq!(next_bound, correct_bounds.upper);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_integration_get_latest_epochs() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let duration = Duration::days(1);
+
+        let d = NaiveDate::from_ymd(2022, 3, 16);
+        let t = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let times_vec = create_multiple_naive_datetimes(d, t, duration, 3);
+        for t in times_vec {
+            let am = Epoch::ActiveModel {
+                epoch: Set(Local.from_utc_datetime(&t).to_owned()),
+                ..Default::default()
+            };
+            am.insert(&db).await?;
+        }
+
+        let latest = get_latest_epochs(&db, 2).await?;
+        let latest_ids = latest.iter().map(|e| e.id).collect::<Vec<_>>();
+
+        assert_eq!(latest_ids, vec![3, 2]);
+        Ok(())
+    }
 }
@@ -1,4 +1,8 @@
+pub mod dad_events;
 pub mod dadded;
 pub mod epochs;
 #[cfg(test)]
 mod integration_utils;
+pub mod opt_outs;
+pub mod room_settings;
+pub mod victims;
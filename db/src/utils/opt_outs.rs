@@ -0,0 +1,137 @@
+use crate::errors::Error;
+use crate::sea_orm::*;
+use crate::OptOut;
+use std::collections::HashSet;
+use tracing::*;
+
+fn scope_condition(room_id: Option<&str>) -> Condition {
+    match room_id {
+        Some(room_id) => Condition::all().add(OptOut::Column::RoomId.eq(room_id)),
+        None => Condition::all().add(OptOut::Column::RoomId.is_null()),
+    }
+}
+
+/// Opts a user out of one room, or every room when `room_id` is `None`
+pub async fn opt_out(
+    db: &DbConn,
+    mxid: &str,
+    room_id: Option<&str>,
+) -> Result<OptOut::Model, Error> {
+    if let Some(opt_out) = OptOut::Entity::find()
+        .filter(scope_condition(room_id).add(OptOut::Column::Mxid.eq(mxid)))
+        .one(db)
+        .await?
+    {
+        Ok(opt_out)
+    } else {
+        let opt_out_model = OptOut::ActiveModel {
+            mxid: Set(mxid.to_owned()),
+            room_id: Set(room_id.map(|r| r.to_owned())),
+            ..Default::default()
+        };
+        let opt_out = opt_out_model.insert(db).await?;
+        info!(
+            "Created OptOut {{ id: {}, mxid: {}, room: {:?} }}",
+            opt_out.id, opt_out.mxid, opt_out.room_id
+        );
+        Ok(opt_out)
+    }
+}
+
+/// Opts a user back in to one room, or removes all of their opt outs when `room_id` is `None`
+pub async fn opt_in(db: &DbConn, mxid: &str, room_id: Option<&str>) -> Result<u64, Error> {
+    let mut condition = Condition::all().add(OptOut::Column::Mxid.eq(mxid));
+    if let Some(room_id) = room_id {
+        condition = condition.add(OptOut::Column::RoomId.eq(room_id));
+    }
+    let res = OptOut::Entity::delete_many()
+        .filter(condition)
+        .exec(db)
+        .await?;
+    info!(
+        "Removed {} OptOuts {{ mxid: {}, room: {:?} }}",
+        res.rows_affected, mxid, room_id
+    );
+    Ok(res.rows_affected)
+}
+
+fn applies_to_room(room_id: &str) -> Condition {
+    Condition::any()
+        .add(OptOut::Column::RoomId.is_null())
+        .add(OptOut::Column::RoomId.eq(room_id))
+}
+
+pub async fn is_opted_out(db: &DbConn, mxid: &str, room_id: &str) -> Result<bool, Error> {
+    let opt_out = OptOut::Entity::find()
+        .filter(
+            Condition::all()
+                .add(OptOut::Column::Mxid.eq(mxid))
+                .add(applies_to_room(room_id)),
+        )
+        .one(db)
+        .await?;
+    Ok(opt_out.is_some())
+}
+
+pub async fn get_opted_out(db: &DbConn, room_id: &str) -> Result<HashSet<String>, Error> {
+    let opt_outs = OptOut::Entity::find()
+        .filter(applies_to_room(room_id))
+        .all(db)
+        .await?;
+    Ok(opt_outs.into_iter().map(|o| o.mxid).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::integration_utils;
+
+    #[tokio::test]
+    async fn test_integration_opt_out_of_room() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+
+        let opt_out_model = opt_out(&db, alice, Some(room_id)).await?;
+        let same_opt_out = opt_out(&db, alice, Some(room_id)).await?;
+
+        assert_eq!(opt_out_model, same_opt_out);
+        assert!(is_opted_out(&db, alice, room_id).await?);
+        assert!(!is_opted_out(&db, alice, "!other:example.org").await?);
+        assert!(!is_opted_out(&db, "@bob:example.org", room_id).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_opt_out_everywhere() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+
+        opt_out(&db, alice, None).await?;
+        opt_out(&db, bob, Some("!other:example.org")).await?;
+
+        assert!(is_opted_out(&db, alice, room_id).await?);
+        let opted_out = get_opted_out(&db, room_id).await?;
+        assert_eq!(opted_out, HashSet::from([alice.to_string()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_opt_in() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+
+        opt_out(&db, alice, None).await?;
+        opt_out(&db, alice, Some(room_id)).await?;
+
+        assert_eq!(opt_in(&db, alice, Some(room_id)).await?, 1);
+        assert!(is_opted_out(&db, alice, room_id).await?);
+        opt_out(&db, alice, Some(room_id)).await?;
+        assert_eq!(opt_in(&db, alice, None).await?, 2);
+        assert!(!is_opted_out(&db, alice, room_id).await?);
+        Ok(())
+    }
+}
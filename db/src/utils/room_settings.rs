@@ -0,0 +1,127 @@
+use crate::errors::Error;
+use crate::sea_orm::*;
+use crate::RoomSettings;
+use tracing::*;
+
+/// A single room setting to store, `None` resets it to the config default
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoomSetting {
+    Enabled(bool),
+    DaddedChance(Option<i64>),
+    LoveMeChance(Option<i64>),
+    Locale(Option<String>),
+    Cooldown(Option<i64>),
+    DadMode(Option<String>),
+}
+
+pub async fn get_room_settings(
+    db: &DbConn,
+    room_id: &str,
+) -> Result<Option<RoomSettings::Model>, Error> {
+    let settings = RoomSettings::Entity::find()
+        .filter(RoomSettings::Column::RoomId.eq(room_id))
+        .one(db)
+        .await?;
+    Ok(settings)
+}
+
+pub async fn get_or_create_room_settings(
+    db: &DbConn,
+    room_id: &str,
+) -> Result<RoomSettings::Model, Error> {
+    if let Some(settings) = get_room_settings(db, room_id).await? {
+        Ok(settings)
+    } else {
+        let settings_model = RoomSettings::ActiveModel {
+            room_id: Set(room_id.to_owned()),
+            enabled: Set(true),
+            dadded_chance: Set(None),
+            love_me_chance: Set(None),
+            locale: Set(None),
+            cooldown: Set(None),
+            dad_mode: Set(None),
+            ..Default::default()
+        };
+        let settings = settings_model.insert(db).await?;
+        info!(
+            "Created RoomSettings {{ id: {}, room: {} }}",
+            settings.id, settings.room_id
+        );
+        Ok(settings)
+    }
+}
+
+pub async fn set_room_setting(
+    db: &DbConn,
+    room_id: &str,
+    setting: RoomSetting,
+) -> Result<RoomSettings::Model, Error> {
+    let settings = get_or_create_room_settings(db, room_id).await?;
+    let mut active_settings: RoomSettings::ActiveModel = settings.into();
+    match setting.clone() {
+        RoomSetting::Enabled(enabled) => active_settings.enabled = Set(enabled),
+        RoomSetting::DaddedChance(chance) => active_settings.dadded_chance = Set(chance),
+        RoomSetting::LoveMeChance(chance) => active_settings.love_me_chance = Set(chance),
+        RoomSetting::Locale(locale) => active_settings.locale = Set(locale),
+        RoomSetting::Cooldown(cooldown) => active_settings.cooldown = Set(cooldown),
+        RoomSetting::DadMode(mode) => active_settings.dad_mode = Set(mode),
+    }
+    let new_settings = active_settings.update(db).await?;
+    info!(
+        "Updated RoomSettings {{ id: {}, room: {} }} with {:?}",
+        new_settings.id, new_settings.room_id, setting
+    );
+    Ok(new_settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::integration_utils;
+
+    #[tokio::test]
+    async fn test_integration_get_or_create_room_settings() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+
+        assert_eq!(get_room_settings(&db, room_id).await?, None);
+        let settings = get_or_create_room_settings(&db, room_id).await?;
+        let same_settings = get_or_create_room_settings(&db, room_id).await?;
+
+        assert_eq!(settings, same_settings);
+        assert_eq!(settings.room_id, room_id);
+        assert!(settings.enabled);
+        assert_eq!(settings.dadded_chance, None);
+        assert_eq!(settings.locale, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_set_room_setting() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+
+        set_room_setting(&db, room_id, RoomSetting::Enabled(false)).await?;
+        set_room_setting(&db, room_id, RoomSetting::DaddedChance(Some(10))).await?;
+        set_room_setting(&db, room_id, RoomSetting::Locale(Some(String::from("de")))).await?;
+        set_room_setting(&db, room_id, RoomSetting::Cooldown(Some(30))).await?;
+        let settings = set_room_setting(
+            &db,
+            room_id,
+            RoomSetting::DadMode(Some(String::from("react"))),
+        )
+        .await?;
+
+        assert!(!settings.enabled);
+        assert_eq!(settings.dadded_chance, Some(10));
+        assert_eq!(settings.love_me_chance, None);
+        assert_eq!(settings.locale, Some(String::from("de")));
+        assert_eq!(settings.cooldown, Some(30));
+        assert_eq!(settings.dad_mode, Some(String::from("react")));
+
+        let settings = set_room_setting(&db, room_id, RoomSetting::DaddedChance(None)).await?;
+        assert_eq!(settings.dadded_chance, None);
+        assert_eq!(get_room_settings(&db, "!other:example.org").await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use crate::errors::Error;
+use crate::sea_orm::*;
+use crate::utils::opt_outs;
+use crate::{Epoch, Victim};
+use std::collections::HashMap;
+use tracing::*;
+
+pub async fn get_or_create_victim_from_epoch(
+    db: &DbConn,
+    epoch_id: u32,
+    room_id: &str,
+    mxid: &str,
+) -> Result<Victim::Model, Error> {
+    if let Some(epoch) = Epoch::Entity::find_by_id(epoch_id).one(db).await? {
+        if let Some(victim) = epoch
+            .find_related(Victim::Entity)
+            .filter(
+                Condition::all()
+                    .add(Victim::Column::RoomId.eq(room_id))
+                    .add(Victim::Column::Mxid.eq(mxid)),
+            )
+            .one(db)
+            .await?
+        {
+            Ok(victim)
+        } else {
+            let victim_model = Victim::ActiveModel {
+                epoch_id: Set(epoch_id),
+                room_id: Set(room_id.to_owned()),
+                mxid: Set(mxid.to_owned()),
+                count: Set(0),
+                ..Default::default()
+            };
+            let victim = victim_model.insert(db).await?;
+            info!(
+                "Created Victim {{ id: {}, epoch: {}, room: {}, mxid: {} }}",
+                victim.id, victim.epoch_id, victim.room_id, victim.mxid
+            );
+            Ok(victim)
+        }
+    } else {
+        Err(Error::EpochNotFound { id: epoch_id })
+    }
+}
+
+pub async fn increament_victim(db: &DbConn, victim_id: u32) -> Result<Victim::Model, Error> {
+    if let Some(victim) = Victim::Entity::find_by_id(victim_id).one(db).await? {
+        let current_count = victim.count;
+        let mut active_victim: Victim::ActiveModel = victim.into();
+        active_victim.count = Set(current_count + 1);
+        let new_victim = active_victim.update(db).await?;
+        info!(
+            "Updated Victim {{ id: {} }} count was: {}, now: {}",
+            new_victim.id, current_count, new_victim.count
+        );
+        Ok(new_victim)
+    } else {
+        Err(Error::VictimNotFound { id: victim_id })
+    }
+}
+
+/// Totals the victims of a room who haven't opted out, optionally limited to the given epochs,
+/// most dadded first
+pub async fn get_leaderboard(
+    db: &DbConn,
+    room_id: &str,
+    epoch_ids: Option<&[u32]>,
+) -> Result<Vec<(String, u32)>, Error> {
+    let mut condition = Condition::all().add(Victim::Column::RoomId.eq(room_id));
+    if let Some(epoch_ids) = epoch_ids {
+        condition = condition.add(Victim::Column::EpochId.is_in(epoch_ids.to_vec()));
+    }
+    let victims = Victim::Entity::find().filter(condition).all(db).await?;
+    let opted_out = opt_outs::get_opted_out(db, room_id).await?;
+
+    let mut totals: HashMap<String, u32> = HashMap::new();
+    for victim in victims.into_iter().filter(|v| !opted_out.contains(&v.mxid)) {
+        *totals.entry(victim.mxid).or_insert(0) += victim.count;
+    }
+    let mut leaderboard = totals.into_iter().collect::<Vec<_>>();
+    leaderboard.sort_by(|v1, v2| v2.1.cmp(&v1.1).then_with(|| v1.0.cmp(&v2.0)));
+    Ok(leaderboard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::integration_utils;
+    use chrono::TimeZone;
+    use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+    async fn create_epoch(db: &DbConn, day: u32) -> Result<Epoch::Model, Error> {
+        let d1 = NaiveDate::from_ymd(2022, 3, day);
+        let t1 = NaiveTime::from_hms_milli(0, 0, 0, 0);
+        let dt1 = NaiveDateTime::new(d1, t1);
+        let epoch_am = Epoch::ActiveModel {
+            epoch: Set(Local.from_utc_datetime(&dt1)),
+            ..Default::default()
+        };
+        Ok(epoch_am.insert(db).await?)
+    }
+
+    async fn dad(db: &DbConn, epoch_id: u32, room_id: &str, mxid: &str) -> Result<(), Error> {
+        let victim = get_or_create_victim_from_epoch(db, epoch_id, room_id, mxid).await?;
+        increament_victim(db, victim.id).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_get_or_create_victim() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let mxid = "@alice:example.org";
+        let epoch = create_epoch(&db, 16).await?;
+
+        let victim = get_or_create_victim_from_epoch(&db, epoch.id, room_id, mxid).await?;
+        let same_victim = get_or_create_victim_from_epoch(&db, epoch.id, room_id, mxid).await?;
+        assert_eq!(victim, same_victim);
+        assert_eq!(victim.mxid, mxid);
+        assert_eq!(victim.count, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_get_or_create_victim_epoch_doesnt_exist() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let res_victim =
+            get_or_create_victim_from_epoch(&db, 1, "!room:example.org", "@alice:example.org")
+                .await;
+        assert_eq!(Error::EpochNotFound { id: 1 }, res_victim.unwrap_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_leaderboard() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+        let old_epoch = create_epoch(&db, 15).await?;
+        let epoch = create_epoch(&db, 16).await?;
+
+        dad(&db, old_epoch.id, room_id, bob).await?;
+        dad(&db, old_epoch.id, room_id, bob).await?;
+        dad(&db, epoch.id, room_id, alice).await?;
+        dad(&db, epoch.id, room_id, bob).await?;
+        dad(&db, epoch.id, "!other:example.org", alice).await?;
+
+        let current = get_leaderboard(&db, room_id, Some(&[epoch.id][..])).await?;
+        let all_time = get_leaderboard(&db, room_id, None).await?;
+        assert_eq!(current, vec![(alice.to_string(), 1), (bob.to_string(), 1)]);
+        assert_eq!(all_time, vec![(bob.to_string(), 3), (alice.to_string(), 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integration_leaderboard_skips_opted_out() -> Result<(), Error> {
+        let db = integration_utils::create_inmemory_db().await?;
+        let room_id = "!room:example.org";
+        let alice = "@alice:example.org";
+        let bob = "@bob:example.org";
+        let epoch = create_epoch(&db, 16).await?;
+
+        dad(&db, epoch.id, room_id, alice).await?;
+        dad(&db, epoch.id, room_id, bob).await?;
+        opt_outs::opt_out(&db, bob, Some(room_id)).await?;
+
+        let leaderboard = get_leaderboard(&db, room_id, None).await?;
+        assert_eq!(leaderboard, vec![(alice.to_string(), 1)]);
+        Ok(())
+    }
+}